@@ -0,0 +1,259 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use core::cmp::Reverse;
+
+use rayon::prelude::*;
+
+use crate::decode::Decoder;
+use crate::encode::Encoder;
+use crate::error::{Error, Result};
+use crate::island::{Island, Islands};
+use crate::rect::Rect;
+use crate::sprite::{crop_sprite, decode_with_islands, sprite_rects, SpriteOptions};
+use crate::stream::paste;
+use crate::types::{Channels, ColorSpace};
+
+/// Default maximum atlas width and height.
+const DEFAULT_MAX_SIZE: u32 = 16384;
+
+/// Options controlling atlas packing.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AtlasOptions {
+    padding: u32,
+    max_width: u32,
+    max_height: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self { padding: 0, max_width: DEFAULT_MAX_SIZE, max_height: DEFAULT_MAX_SIZE }
+    }
+}
+
+impl AtlasOptions {
+    /// Creates default options: no padding, atlas of at most 16384x16384.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns new options keeping `padding` transparent (all-zero) pixels between the
+    /// packed images, as well as between the images and the edges of the atlas.
+    #[inline]
+    #[must_use]
+    pub const fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns new options with modified maximum atlas dimensions.
+    #[inline]
+    #[must_use]
+    pub const fn with_max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_width = width;
+        self.max_height = height;
+        self
+    }
+
+    /// Returns the padding around the packed images in pixels.
+    #[inline]
+    pub const fn padding(&self) -> u32 {
+        self.padding
+    }
+
+    /// Returns the maximum atlas width and height.
+    #[inline]
+    pub const fn max_size(&self) -> (u32, u32) {
+        (self.max_width, self.max_height)
+    }
+}
+
+/// Images packed into a single atlas.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Atlas {
+    /// Encoded QOI image of the atlas, with one island per packed image
+    pub data: Vec<u8>,
+    /// Rectangle of each packed image in the atlas, in the order of the inputs
+    pub rects: Vec<Rect>,
+}
+
+/// Bottom-left skyline packer: the top edge of the packed area is kept as a list of
+/// horizontal segments, and each new rectangle is placed as low as possible on it.
+struct Skyline {
+    width: u32,
+    max_height: u32,
+    // (x, y, width) of each segment, left to right, covering the full width
+    nodes: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+    fn new(width: u32, max_height: u32, origin: u32) -> Self {
+        let nodes = vec![(origin, origin, width.saturating_sub(origin))];
+        Self { width, max_height, nodes }
+    }
+
+    /// Returns the lowest position at which a rectangle starting on the `i`-th segment
+    /// would rest, if it fits.
+    fn fit(&self, i: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.nodes[i].0;
+        if u64::from(x) + u64::from(width) > u64::from(self.width) {
+            return None;
+        }
+        let end = x + width;
+        let y = self.nodes[i..].iter().take_while(|node| node.0 < end).map(|node| node.1).max()?;
+        if u64::from(y) + u64::from(height) > u64::from(self.max_height) {
+            return None;
+        }
+        Some(y)
+    }
+
+    /// Places a rectangle of the given size and returns its top-left corner, if it fits.
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (i, y) = (0..self.nodes.len())
+            .filter_map(|i| self.fit(i, width, height).map(|y| (i, y)))
+            .min_by_key(|&(i, y)| (y, self.nodes[i].0))?;
+        let x = self.nodes[i].0;
+        let end = x + width;
+        // drop the segments covered by the new one, shortening the last one
+        let mut j = i;
+        while j < self.nodes.len() && self.nodes[j].0 < end {
+            let (nx, ny, nw) = self.nodes[j];
+            if nx + nw > end {
+                self.nodes[j] = (end, ny, nx + nw - end);
+                break;
+            }
+            j += 1;
+        }
+        self.nodes.splice(i..j, Some((x, y + height, width)));
+        self.nodes.dedup_by(|next, prev| {
+            let merge = prev.1 == next.1;
+            if merge {
+                prev.2 += next.2;
+            }
+            merge
+        });
+        Some((x, y))
+    }
+}
+
+/// Packs rectangles of the given sizes into an atlas as square as possible; returns
+/// the packed rectangles in input order along with the atlas dimensions.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss, clippy::cast_sign_loss)]
+fn pack(sizes: &[(u32, u32)], options: &AtlasOptions) -> Result<(Vec<Rect>, u32, u32)> {
+    let AtlasOptions { padding, max_width, max_height } = *options;
+    let too_small = Error::AtlasTooSmall { width: max_width, height: max_height };
+    // each image claims its own size plus the padding to its right and bottom
+    let slots = sizes
+        .iter()
+        .map(|&(w, h)| (w.saturating_add(padding), h.saturating_add(padding)))
+        .collect::<Vec<_>>();
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| (Reverse(slots[i].1), Reverse(slots[i].0), i));
+
+    let widest = slots.iter().map(|s| u64::from(s.0)).max().unwrap_or(0) + u64::from(padding);
+    let area = slots.iter().map(|s| u64::from(s.0) * u64::from(s.1)).sum::<u64>();
+    if widest > u64::from(max_width) {
+        return Err(too_small);
+    }
+    // start from a roughly square atlas and widen it until everything fits
+    let square = (area as f64).sqrt().ceil() as u64;
+    let mut width = widest.max(square).min(u64::from(max_width)) as u32;
+    loop {
+        let mut skyline = Skyline::new(width, max_height, padding);
+        let mut rects = vec![Rect::default(); sizes.len()];
+        let fits = order.iter().all(|&i| {
+            skyline.insert(slots[i].0, slots[i].1).map_or(false, |(x, y)| {
+                rects[i] = Rect::new(x, y, sizes[i].0, sizes[i].1);
+                true
+            })
+        });
+        if fits {
+            let right = rects.iter().map(|r| r.right() + padding).max().unwrap_or(padding);
+            let bottom = rects.iter().map(|r| r.bottom() + padding).max().unwrap_or(padding);
+            return Ok((rects, right.max(1), bottom.max(1)));
+        }
+        if width == max_width {
+            return Err(too_small);
+        }
+        width = width.saturating_mul(2).min(max_width);
+    }
+}
+
+/// Packs raw images with the given number of channels into an atlas and encodes it.
+fn pack_impl(
+    images: &[(Vec<u8>, u32, u32)], channels: Channels, colorspace: ColorSpace,
+    options: &AtlasOptions,
+) -> Result<Atlas> {
+    let sizes = images.iter().map(|&(_, w, h)| (w, h)).collect::<Vec<_>>();
+    let (rects, width, height) = pack(&sizes, options)?;
+    let n = channels.as_u8() as usize;
+    let mut pixels = vec![0; width as usize * height as usize * n];
+    for ((image, _, _), &rect) in images.iter().zip(&rects) {
+        paste(&mut pixels, width, n, rect, image);
+    }
+    let mut islands = rects
+        .iter()
+        .zip(1..)
+        .map(|(&rect, id)| Island { id: Some(id), ..Island::new(rect) })
+        .collect::<Vec<_>>();
+    // island tables are always in raster order, the ids lead back to the inputs
+    islands.sort_by_key(Island::raster_key);
+    let islands = Islands { islands, ..Islands::default() };
+    let data = Encoder::new(&pixels, width, height)?
+        .with_colorspace(colorspace)
+        .encode_given_islands_to_vec(&islands)?;
+    Ok(Atlas { data, rects })
+}
+
+/// Packs encoded images into a single atlas, encoded as a QOI image.
+///
+/// The images are placed with a bottom-left skyline packer, tallest first, into a
+/// roughly square atlas that is widened up to the maximum width until they all fit.
+/// The rectangle of the `k`-th image is stored in the island table as an island with
+/// the id `k + 1`, so the atlas carries its own UV map; like any island table, it's in
+/// raster order rather than in the order of the images.
+///
+/// The atlas is RGBA if any of the images is, and RGB otherwise; it takes the color
+/// space of the first image.
+pub fn pack_atlas(images: &[impl AsRef<[u8]> + Sync], options: &AtlasOptions) -> Result<Atlas> {
+    let headers = images
+        .iter()
+        .map(|data| Ok(*Decoder::new(data.as_ref())?.header()))
+        .collect::<Result<Vec<_>>>()?;
+    let channels = if headers.iter().any(|header| header.channels.is_rgba()) {
+        Channels::Rgba
+    } else {
+        Channels::Rgb
+    };
+    let colorspace = headers.first().map_or_else(ColorSpace::default, |header| header.colorspace);
+    let decoded = images
+        .par_iter()
+        .map(|data| {
+            let mut decoder = Decoder::new(data.as_ref())?.with_channels(channels);
+            let header = *decoder.header();
+            Ok((decoder.decode_to_vec()?, header.width, header.height))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    pack_impl(&decoded, channels, colorspace, options)
+}
+
+/// Packs the islands of an encoded image into an atlas, encoded as a QOI image.
+///
+/// The islands are cropped out the same way as by
+/// [`extract_sprites_from_qoi`](crate::extract_sprites_from_qoi) and packed the same
+/// way as by [`pack_atlas`]; the source rectangle of each packed island is returned
+/// along with the atlas.
+pub fn pack_islands(
+    data: impl AsRef<[u8]>, sprite_options: &SpriteOptions, options: &AtlasOptions,
+) -> Result<(Atlas, Vec<Rect>)> {
+    let (header, pixels, islands) = decode_with_islands(data.as_ref(), sprite_options)?;
+    let sources = sprite_rects(&pixels, &header, &islands, sprite_options);
+    let padding = sprite_options.padding();
+    let images = sources
+        .par_iter()
+        .map(|&rect| crop_sprite(&pixels, &header, rect, padding))
+        .collect::<Vec<_>>();
+    let atlas = pack_impl(&images, header.channels, header.colorspace, options)?;
+    Ok((atlas, sources))
+}
@@ -0,0 +1,157 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::vec::Vec;
+
+use core::iter::once;
+
+use serde::{Deserialize, Serialize};
+
+use crate::decode::Decoder;
+use crate::error::Result;
+use crate::header::Header;
+use crate::island::Islands;
+use crate::mask::IslandMask;
+
+/// Segmentation of a single COCO annotation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CocoSegmentation {
+    /// Outer island boundaries as flattened `[x0, y0, x1, y1, ...]` polygons
+    Polygons(Vec<Vec<u32>>),
+    /// Uncompressed run-length encoding of the island mask over the whole image,
+    /// in column-major order and starting with a run of background pixels
+    Rle {
+        /// Run lengths, alternating between background and foreground
+        counts: Vec<u64>,
+        /// Image size as `[height, width]`
+        size: [u32; 2],
+    },
+}
+
+impl CocoSegmentation {
+    /// Run-length encodes a mask in column-major order over a `width x height` image.
+    fn from_mask(mask: &IslandMask, width: u32, height: u32) -> Self {
+        // pixels outside of the bounding box are background, so the runs may only
+        // change inside of it or where a column enters or leaves it
+        let rect = mask.rect();
+        let mut counts = Vec::new();
+        let (mut start, mut value) = (0, false);
+        let mut flip = |pos: u64| {
+            counts.push(pos - start);
+            start = pos;
+        };
+        for x in rect.x..rect.right() {
+            let column = u64::from(x) * u64::from(height);
+            if value && rect.y > 0 {
+                flip(column);
+                value = false;
+            }
+            for y in rect.y..rect.bottom() {
+                if mask.contains(x, y) != value {
+                    flip(column + u64::from(y));
+                    value = !value;
+                }
+            }
+            if value && rect.bottom() < height {
+                flip(column + u64::from(rect.bottom()));
+                value = false;
+            }
+        }
+        counts.push(u64::from(width) * u64::from(height) - start);
+        Self::Rle { counts, size: [height, width] }
+    }
+}
+
+/// Single object annotation in the COCO format.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CocoAnnotation {
+    /// Annotation identifier (1-based island index)
+    pub id: u64,
+    /// Identifier of the annotated image
+    pub image_id: u64,
+    /// Category of the annotated object
+    pub category_id: u64,
+    /// Bounding box as `[x, y, width, height]`
+    pub bbox: [u32; 4],
+    /// Number of island pixels (bounding box area if neither stats nor masks are known)
+    pub area: u64,
+    /// Island segmentation, if masks or contours are available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segmentation: Option<CocoSegmentation>,
+    /// Always 0, islands are single objects
+    pub iscrowd: u8,
+}
+
+/// COCO-style `annotations` list, serializing as `{"annotations": [...]}`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CocoAnnotations {
+    /// Annotations, one per island and in the same order
+    pub annotations: Vec<CocoAnnotation>,
+}
+
+impl CocoAnnotations {
+    /// Converts islands of an image to COCO annotations.
+    ///
+    /// Segmentations are taken from the masks (as run-length encodings) if present,
+    /// otherwise from the contours (as polygons, holes are dropped); if neither is
+    /// present, the annotations only have bounding boxes.
+    pub fn from_islands(
+        islands: &Islands, header: &Header, image_id: u64, category_id: u64,
+    ) -> Self {
+        let annotations = islands
+            .iter()
+            .enumerate()
+            .map(|(i, island)| {
+                let rect = island.rect;
+                let mask = islands.masks.get(i);
+                let area = match (island.stats, mask) {
+                    (Some(stats), _) => u64::from(stats.pixel_count),
+                    (None, Some(mask)) => mask.count() as u64,
+                    (None, None) => rect.area() as u64,
+                };
+                let segmentation = match (mask, islands.contours.get(i)) {
+                    (Some(mask), _) => {
+                        Some(CocoSegmentation::from_mask(mask, header.width, header.height))
+                    }
+                    (None, Some(contours)) => Some(CocoSegmentation::Polygons(
+                        contours
+                            .iter()
+                            .filter(|contour| !contour.hole)
+                            .map(|contour| {
+                                contour
+                                    .points
+                                    .iter()
+                                    .flat_map(|&(x, y)| once(x).chain(once(y)))
+                                    .collect()
+                            })
+                            .collect(),
+                    )),
+                    (None, None) => None,
+                };
+                CocoAnnotation {
+                    id: i as u64 + 1,
+                    image_id,
+                    category_id,
+                    bbox: [rect.x, rect.y, rect.width, rect.height],
+                    area,
+                    segmentation,
+                    iscrowd: 0,
+                }
+            })
+            .collect();
+        Self { annotations }
+    }
+}
+
+/// Reads the island table of an encoded image (without decoding the pixels) and
+/// converts it to COCO annotations, see [`CocoAnnotations::from_islands`].
+///
+/// Annotation ids are only unique within the image; renumber them when merging
+/// annotations of several images into one dataset.
+pub fn coco_annotations(
+    data: impl AsRef<[u8]>, image_id: u64, category_id: u64,
+) -> Result<CocoAnnotations> {
+    let mut decoder = Decoder::new(&data)?;
+    let header = *decoder.header();
+    let islands = decoder.islands()?;
+    Ok(CocoAnnotations::from_islands(&islands, &header, image_id, category_id))
+}
@@ -13,5 +13,24 @@ pub const QOI_PADDING: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0x01]; // 7 zeros and one
 pub const QOI_PADDING_SIZE: usize = 8;
 
 pub const QOI_MAGIC: u32 = u32::from_be_bytes(*b"qoif");
+pub const QOI_DELTA_MAGIC: u32 = u32::from_be_bytes(*b"qoid");
+pub const QOI_DELTA_HEADER_SIZE: usize = 17;
 
 pub const QOI_PIXELS_MAX: usize = 400_000_000;
+
+pub const QOI_ISLAND_SIZE: usize = 16;
+
+// optional island data lives in an extension section following the island table, so that
+// readers unaware of it still find the plain table right after the pixel stream
+pub const QOI_EXTENSION_MAGIC: u32 = u32::from_be_bytes(*b"qoix");
+pub const QOI_EXTENSION_SIZE: usize = 5; // magic and flags
+
+pub const QOI_CANVAS_SIZE: usize = 16;
+
+pub const QOI_FLAG_ISLAND_STATS: u8 = 0x01; // per-island fields include pixel statistics
+pub const QOI_FLAG_ISLAND_MASKS: u8 = 0x02; // island masks section follows the per-island fields
+pub const QOI_FLAG_ISLAND_STREAMS: u8 = 0x04; // each island is also encoded as its own sub-stream
+pub const QOI_FLAG_SPARSE: u8 = 0x08; // pixel stream is replaced by a background colour
+pub const QOI_FLAG_ISLAND_CONTOURS: u8 = 0x10; // island contours section follows the sub-streams
+pub const QOI_FLAG_ISLAND_IDS: u8 = 0x20; // per-island fields include persistent tracking ids
+pub const QOI_FLAG_TRIMMED: u8 = 0x40; // canvas follows the extension magic and flags
@@ -0,0 +1,255 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use core::cmp::Ordering::Equal;
+
+use bytemuck::cast_slice;
+
+use crate::error::{Error, Result};
+use crate::island::Connectivity;
+use crate::mask::IslandMask;
+use crate::utils::Writer;
+
+/// Closed polygon tracing the boundary between an island and the background.
+///
+/// Vertices lie on pixel corners in image coordinates, i.e. the pixel at column `x`
+/// and row `y` spans from `(x, y)` to `(x + 1, y + 1)`. Outer boundaries run clockwise
+/// (in image coordinates, with the y axis pointing down) and holes counter-clockwise;
+/// the last vertex is implicitly connected to the first one.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Contour {
+    /// Polygon vertices as (column, row)
+    pub points: Vec<(u32, u32)>,
+    /// True if the contour is the boundary of a hole inside the island
+    pub hole: bool,
+}
+
+impl Contour {
+    /// Returns true if the point lies inside the polygon (even-odd rule).
+    ///
+    /// To hit-test the pixel at column `x` and row `y`, pass its centre `(x + 0.5, y + 0.5)`;
+    /// a pixel belongs to an island if it lies inside an odd number of its contours.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let n = self.points.len();
+        let mut inside = false;
+        for i in 0..n {
+            let (x0, y0) = point(self.points[i]);
+            let (x1, y1) = point(self.points[(i + 1) % n]);
+            if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+}
+
+#[inline]
+fn point((x, y): (u32, u32)) -> (f64, f64) {
+    (f64::from(x), f64::from(y))
+}
+
+// directions of boundary edges; turning right means going clockwise
+const RIGHT: u8 = 0;
+const DOWN: u8 = 1;
+const LEFT: u8 = 2;
+const UP: u8 = 3;
+
+/// Traces all contours of a mask, outer boundaries and holes alike, in raster order
+/// of their top-left vertex.
+///
+/// Where two foreground pixels only touch diagonally, the boundary either passes
+/// between them (4-connectivity) or goes around both (8-connectivity). If the
+/// tolerance is positive, the polygons are simplified with the Douglas-Peucker
+/// algorithm; otherwise, only collinear vertices are dropped.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub fn trace(mask: &IslandMask, connectivity: Connectivity, tolerance: f32) -> Vec<Contour> {
+    let rect = mask.rect();
+    let (width, height) = (rect.width as usize, rect.height as usize);
+    let bits = mask.to_vec();
+    let fg = |x: usize, y: usize| x < width && y < height && bits[y * width + x];
+
+    // directed boundary edges with the foreground on the right, keyed by their start vertex
+    let stride = width + 1;
+    let mut edges = vec![0_u8; stride * (height + 1)];
+    for y in 0..height {
+        for x in (0..width).filter(|&x| fg(x, y)) {
+            let vertex = y * stride + x;
+            if y == 0 || !fg(x, y - 1) {
+                edges[vertex] |= 1 << RIGHT;
+            }
+            if !fg(x + 1, y) {
+                edges[vertex + 1] |= 1 << DOWN;
+            }
+            if !fg(x, y + 1) {
+                edges[vertex + stride + 1] |= 1 << LEFT;
+            }
+            if x == 0 || !fg(x - 1, y) {
+                edges[vertex + stride] |= 1 << UP;
+            }
+        }
+    }
+
+    let turns = if connectivity.is_eight() { [3, 0, 1] } else { [1, 0, 3] };
+    let mut contours = Vec::new();
+    for start in 0..edges.len() {
+        while edges[start] != 0 {
+            let first = edges[start].trailing_zeros() as u8;
+            edges[start] &= !(1 << first);
+            let (mut vertex, mut dir) = (start, first);
+            let mut points = Vec::new();
+            loop {
+                vertex = match dir {
+                    RIGHT => vertex + 1,
+                    DOWN => vertex + stride,
+                    LEFT => vertex - 1,
+                    _ => vertex - stride,
+                };
+                // every edge has exactly one successor, so the loop is closed once the
+                // successor is the edge it started with
+                let next = match turns
+                    .iter()
+                    .map(|turn| (dir + turn) % 4)
+                    .find(|&d| edges[vertex] & (1 << d) != 0 || (vertex == start && d == first))
+                {
+                    Some(next) => next,
+                    None => break, // can't happen for a well-formed set of edges
+                };
+                if next != dir {
+                    let (x, y) = (vertex % stride, vertex / stride);
+                    points.push((rect.x + x as u32, rect.y + y as u32));
+                }
+                if vertex == start && next == first {
+                    break;
+                }
+                edges[vertex] &= !(1 << next);
+                dir = next;
+            }
+            // start from the first vertex in raster order (closing the loop added it last)
+            points.rotate_right(1);
+            let hole = signed_area(&points) < 0;
+            contours.push(Contour { points: simplify(points, tolerance), hole });
+        }
+    }
+    contours
+}
+
+/// Twice the signed area of a polygon, positive for clockwise ones (y axis pointing down).
+#[allow(clippy::cast_possible_wrap)]
+fn signed_area(points: &[(u32, u32)]) -> i64 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let ((x0, y0), (x1, y1)) = (points[i], points[(i + 1) % n]);
+            i64::from(x0) * i64::from(y1) - i64::from(x1) * i64::from(y0)
+        })
+        .sum()
+}
+
+/// Distance from `p` to the segment between `a` and `b`.
+fn distance(p: (u32, u32), a: (u32, u32), b: (u32, u32)) -> f64 {
+    let ((px, py), (ax, ay), (bx, by)) = (point(p), point(a), point(b));
+    let (dx, dy) = (bx - ax, by - ay);
+    let len2 = dx.mul_add(dx, dy * dy);
+    let dot = (px - ax).mul_add(dx, (py - ay) * dy);
+    let t = if len2 == 0. { 0. } else { (dot / len2).clamp(0., 1.) };
+    (ax + t * dx - px).hypot(ay + t * dy - py)
+}
+
+/// Marks the vertices to keep between `i` and `j` (exclusive; `j` may wrap around).
+fn douglas_peucker(points: &[(u32, u32)], i: usize, j: usize, tolerance: f64, keep: &mut [bool]) {
+    let (start, end) = (points[i], points[j % points.len()]);
+    let farthest = (i + 1..j)
+        .map(|k| (k, distance(points[k], start, end)))
+        .max_by(|(_, d0), (_, d1)| d0.partial_cmp(d1).unwrap_or(Equal));
+    if let Some((k, dist)) = farthest {
+        if dist > tolerance {
+            keep[k] = true;
+            douglas_peucker(points, i, k, tolerance, keep);
+            douglas_peucker(points, k, j, tolerance, keep);
+        }
+    }
+}
+
+/// Simplifies a closed polygon, always keeping at least three vertices.
+fn simplify(points: Vec<(u32, u32)>, tolerance: f32) -> Vec<(u32, u32)> {
+    let n = points.len();
+    if tolerance <= 0. || n <= 3 {
+        return points;
+    }
+    // split the ring at the vertex farthest from the first one
+    let farthest_from = |a, b, range: &mut dyn Iterator<Item = usize>| {
+        range.max_by(|&i, &j| {
+            distance(points[i], a, b).partial_cmp(&distance(points[j], a, b)).unwrap_or(Equal)
+        })
+    };
+    let anchor = farthest_from(points[0], points[0], &mut (1..n)).unwrap_or(1);
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[anchor] = true;
+    douglas_peucker(&points, 0, anchor, f64::from(tolerance), &mut keep);
+    douglas_peucker(&points, anchor, n, f64::from(tolerance), &mut keep);
+    if keep.iter().filter(|&&k| k).count() < 3 {
+        // degenerate result: keep the vertex farthest from the line through both anchors
+        let (a, b) = (points[0], points[anchor]);
+        if let Some(k) = farthest_from(a, b, &mut (1..n).filter(|&k| k != anchor)) {
+            keep[k] = true;
+        }
+    }
+    points.into_iter().zip(keep).filter_map(|(p, keep)| if keep { Some(p) } else { None }).collect()
+}
+
+/// Number of bytes taken by the serialized contours of a single island.
+pub fn encoded_len(contours: &[Contour]) -> usize {
+    4 + contours.iter().map(|contour| 5 + 8 * contour.points.len()).sum::<usize>()
+}
+
+/// Serializes the contours of a single island: the number of contours, followed by
+/// the number of vertices, the hole flag and the vertices of each contour.
+#[allow(clippy::cast_possible_truncation)]
+pub fn encode<W: Writer>(mut buf: W, contours: &[Contour]) -> Result<W> {
+    buf = buf.write_many(&(contours.len() as u32).to_be_bytes())?;
+    for contour in contours {
+        buf = buf.write_many(&(contour.points.len() as u32).to_be_bytes())?;
+        buf = buf.write_one(u8::from(contour.hole))?;
+        for &(x, y) in &contour.points {
+            buf = buf.write_many(&x.to_be_bytes())?;
+            buf = buf.write_many(&y.to_be_bytes())?;
+        }
+    }
+    Ok(buf)
+}
+
+#[inline]
+fn take<'a>(data: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if data.len() < n {
+        return Err(Error::UnexpectedBufferEnd);
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+#[inline]
+fn take_u32(data: &mut &[u8]) -> Result<u32> {
+    let v = take(data, 4)?;
+    Ok(u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+}
+
+/// Deserializes the contours of a single island, advancing the input slice past them.
+pub fn decode(data: &mut &[u8]) -> Result<Vec<Contour>> {
+    let n_contours = take_u32(data)? as usize;
+    // each contour takes at least 5 bytes, don't trust the count blindly
+    let mut contours = Vec::with_capacity(n_contours.min(data.len() / 5));
+    for _ in 0..n_contours {
+        let n_points = take_u32(data)? as usize;
+        let hole = take(data, 1)?[0] != 0;
+        let v = cast_slice::<_, [u8; 4]>(take(data, n_points.saturating_mul(8))?);
+        let points = v
+            .chunks_exact(2)
+            .map(|p| (u32::from_be_bytes(p[0]), u32::from_be_bytes(p[1])))
+            .collect();
+        contours.push(Contour { points, hole });
+    }
+    Ok(contours)
+}
@@ -5,14 +5,20 @@ use std::io::Read;
 
 // TODO: can be removed once https://github.com/rust-lang/rust/issues/74985 is stable
 use bytemuck::{cast_slice_mut, Pod};
+use rayon::prelude::*;
 
 use crate::consts::{
-    QOI_HEADER_SIZE, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA, QOI_OP_RUN,
+    QOI_EXTENSION_SIZE, QOI_HEADER_SIZE, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB,
+    QOI_OP_RGBA, QOI_OP_RUN, QOI_PADDING_SIZE,
 };
+use crate::contour::Contour;
 use crate::error::{Error, Result};
-use crate::header::Header;
-use crate::island::Islands;
+use crate::header::{Canvas, Header};
+use crate::island::{decode_island_record, Islands};
+use crate::mask::IslandMask;
 use crate::pixel::{Pixel, SupportedChannels};
+use crate::rect::Rect;
+use crate::stream::crop;
 use crate::types::Channels;
 use crate::utils::{cold, unlikely};
 
@@ -22,7 +28,7 @@ const QOI_OP_DIFF_END: u8 = QOI_OP_DIFF | 0x3f;
 const QOI_OP_LUMA_END: u8 = QOI_OP_LUMA | 0x3f;
 
 #[inline]
-fn decode_impl_slice<const N: usize, const RGBA: bool>(data: &[u8], out: &mut [u8], n_encode: u32) -> Result<usize>
+fn decode_impl_slice<const N: usize, const RGBA: bool>(data: &[u8], out: &mut [u8]) -> Result<usize>
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
@@ -35,12 +41,7 @@ where
     let mut px = Pixel::<N>::new().with_a(0xff);
     let mut px_rgba: Pixel<4>;
 
-    let mut encode_count = 0;
     while let [px_out, ptail @ ..] = pixels {
-        encode_count += 1;
-        if encode_count > n_encode {
-            break
-        }
         pixels = ptail;
         match data {
             [b1 @ QOI_OP_INDEX..=QOI_OP_INDEX_END, dtail @ ..] => {
@@ -99,13 +100,13 @@ where
 
 #[inline]
 fn decode_impl_slice_all(
-    data: &[u8], out: &mut [u8], n_encode: u32, channels: u8, src_channels: u8,
+    data: &[u8], out: &mut [u8], channels: u8, src_channels: u8,
 ) -> Result<usize> {
     match (channels, src_channels) {
-        (3, 3) => decode_impl_slice::<3, false>(data, out, n_encode),
-        (3, 4) => decode_impl_slice::<3, true>(data, out, n_encode),
-        (4, 3) => decode_impl_slice::<4, false>(data, out, n_encode),
-        (4, 4) => decode_impl_slice::<4, true>(data, out, n_encode),
+        (3, 3) => decode_impl_slice::<3, false>(data, out),
+        (3, 4) => decode_impl_slice::<3, true>(data, out),
+        (4, 3) => decode_impl_slice::<4, false>(data, out),
+        (4, 4) => decode_impl_slice::<4, true>(data, out),
         _ => {
             cold();
             Err(Error::InvalidChannels { channels })
@@ -113,6 +114,176 @@ fn decode_impl_slice_all(
     }
 }
 
+/// Decodes the pixel stream up to the last pixel of the region, only writing out
+/// the pixels that fall within it (tightly packed, row by row).
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+fn decode_region_impl<const N: usize, const RGBA: bool>(
+    data: &[u8], out: &mut [u8], width: usize, rect: Rect,
+) where
+    Pixel<N>: SupportedChannels,
+    [u8; N]: Pod,
+{
+    let out = cast_slice_mut::<_, [u8; N]>(out);
+    let (x0, x1) = (rect.x as usize, rect.right() as usize);
+    let (y0, y1) = (rect.y as usize, rect.bottom() as usize);
+    let end = (y1 - 1) * width + x1; // one past the last pixel of the region
+    let mut data = data;
+
+    let mut index = [Pixel::<4>::new(); 256];
+    let mut px = Pixel::<N>::new().with_a(0xff);
+    let (mut pos, mut col, mut row) = (0, 0, 0);
+
+    while pos < end {
+        let mut run = 1;
+        match data {
+            [b1 @ QOI_OP_INDEX..=QOI_OP_INDEX_END, dtail @ ..] => {
+                px.update(index[*b1 as usize]);
+                data = dtail;
+            }
+            [b1 @ QOI_OP_RUN..=QOI_OP_RUN_END, dtail @ ..] => {
+                run += (b1 & 0x3f) as usize;
+                data = dtail;
+            }
+            _ => {
+                match data {
+                    [QOI_OP_RGB, r, g, b, dtail @ ..] => {
+                        px.update_rgb(*r, *g, *b);
+                        data = dtail;
+                    }
+                    [QOI_OP_RGBA, r, g, b, a, dtail @ ..] if RGBA => {
+                        px.update_rgba(*r, *g, *b, *a);
+                        data = dtail;
+                    }
+                    [b1 @ QOI_OP_DIFF..=QOI_OP_DIFF_END, dtail @ ..] => {
+                        px.update_diff(*b1);
+                        data = dtail;
+                    }
+                    [b1 @ QOI_OP_LUMA..=QOI_OP_LUMA_END, b2, dtail @ ..] => {
+                        px.update_luma(*b1, *b2);
+                        data = dtail;
+                    }
+                    _ => {
+                        cold();
+                    }
+                }
+                let px_rgba = px.as_rgba(0xff);
+                index[px_rgba.hash_index() as usize] = px_rgba;
+            }
+        }
+
+        // emit the pixel `run` times, one row segment at a time
+        let mut count = run.min(end - pos);
+        pos += count;
+        while count != 0 {
+            let step = count.min(width - col);
+            let (from, to) = (col.max(x0), (col + step).min(x1));
+            if row >= y0 && from < to {
+                let offset = (row - y0) * (x1 - x0);
+                out[offset + from - x0..offset + to - x0].fill(px.into());
+            }
+            count -= step;
+            col += step;
+            if col == width {
+                col = 0;
+                row += 1;
+            }
+        }
+    }
+}
+
+#[inline]
+fn decode_region_all(
+    data: &[u8], out: &mut [u8], width: usize, rect: Rect, channels: u8, src_channels: u8,
+) -> Result<()> {
+    match (channels, src_channels) {
+        (3, 3) => decode_region_impl::<3, false>(data, out, width, rect),
+        (3, 4) => decode_region_impl::<3, true>(data, out, width, rect),
+        (4, 3) => decode_region_impl::<4, false>(data, out, width, rect),
+        (4, 4) => decode_region_impl::<4, true>(data, out, width, rect),
+        _ => {
+            cold();
+            return Err(Error::InvalidChannels { channels });
+        }
+    }
+    Ok(())
+}
+
+/// Reconstructs a region of a sparse image: the region is filled with the background
+/// colour (stored in place of the pixel stream), and then the bounding boxes of all
+/// islands overlapping it are decoded from their sub-streams and pasted on top.
+fn decode_sparse(
+    body: &[u8], out: &mut [u8], header: &Header, rect: Rect, channels: u8,
+) -> Result<()> {
+    let src_channels = header.channels.as_u8();
+    let background = body.get(..4).ok_or(Error::UnexpectedBufferEnd)?;
+    let mut px = [background[0], background[1], background[2], 0xff];
+    if src_channels == 4 {
+        px[3] = background[3];
+    }
+    let n = channels as usize;
+    out.chunks_exact_mut(n).for_each(|out| out.copy_from_slice(&px[..n]));
+
+    let table = body.get(header.island_table_offset()..).ok_or(Error::UnexpectedBufferEnd)?;
+    let mut overlapping = Vec::new();
+    for index in 0..header.n_islands() {
+        let (island, stream) = decode_island_record(table, header, index)?;
+        if let Some(overlap) = island.intersection(&rect) {
+            overlapping.push((island, overlap, &table[stream.unwrap_or_default()]));
+        }
+    }
+    let decoded = overlapping
+        .par_iter()
+        .map(|&(island, _, stream)| {
+            let mut pixels = vec![0; island.area() * n];
+            let _ = decode_impl_slice_all(stream, &mut pixels, channels, src_channels)?;
+            Ok(pixels)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // overlapping bounding boxes hold the same pixels, so the pasting order doesn't matter
+    for (&(island, overlap, _), pixels) in overlapping.iter().zip(&decoded) {
+        let (src_x, dst_x) = ((overlap.x - island.x) as usize, (overlap.x - rect.x) as usize);
+        let len = overlap.width as usize * n;
+        for y in overlap.y..overlap.bottom() {
+            let src = ((y - island.y) as usize * island.width as usize + src_x) * n;
+            let dst = ((y - rect.y) as usize * rect.width as usize + dst_x) * n;
+            out[dst..dst + len].copy_from_slice(&pixels[src..src + len]);
+        }
+    }
+    Ok(())
+}
+
+/// Moves an image decoded to the start of the buffer to its place on the canvas, and
+/// clears the rest of the canvas.
+fn restore_canvas(buf: &mut [u8], header: &Header, canvas: &Canvas, channels: usize) {
+    let (width, height) = (header.width as usize, header.height as usize);
+    let (x0, y0) = (canvas.x as usize, canvas.y as usize);
+    let (row, canvas_row) = (width * channels, canvas.width as usize * channels);
+    // rows only move forward, so moving the last one first never overwrites any
+    for y in (0..height).rev() {
+        buf.copy_within(y * row..(y + 1) * row, (y0 + y) * canvas_row + x0 * channels);
+    }
+    for (y, line) in buf.chunks_exact_mut(canvas_row).take(canvas.height as usize).enumerate() {
+        if (y0..y0 + height).contains(&y) {
+            line[..x0 * channels].fill(0);
+            line[x0 * channels + row..].fill(0);
+        } else {
+            line.fill(0);
+        }
+    }
+}
+
+/// Checks that the region is non-empty and lies within the image.
+#[inline]
+fn validate_region(rect: Rect, header: &Header) -> Result<()> {
+    if unlikely(rect.is_empty() || rect.clip_to(header) != Some(rect)) {
+        let Rect { x, y, width, height } = rect;
+        return Err(Error::InvalidRegion { x, y, width, height });
+    }
+    Ok(())
+}
+
 /// Decode the image into a pre-allocated buffer.
 ///
 /// Note: the resulting number of channels will match the header. In order to change
@@ -133,10 +304,20 @@ pub fn decode_to_buf(buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>) -> Result<He
 pub fn decode_qoi(data: impl AsRef<[u8]>) -> Result<(Header, Vec<u8>, Islands)> {
     let mut decoder = Decoder::new(&data)?;
     let out = decoder.decode_to_vec()?;
-    let islands = decoder.decode_to_islands()?;
+    let islands = decoder.islands()?;
     Ok((*decoder.header(), out, islands))
 }
 
+/// Decode the island table without decoding the pixel stream.
+///
+/// The pixel stream is skipped based on its size stored in the header, which makes
+/// this much cheaper than [`decode_qoi`] when only the islands are needed.
+#[cfg(any(feature = "std", feature = "alloc"))]
+#[inline]
+pub fn decode_islands(data: impl AsRef<[u8]>) -> Result<Islands> {
+    Decoder::new(&data)?.islands()
+}
+
 /// Decode the image header from a slice of bytes.
 #[inline]
 pub fn decode_header(data: impl AsRef<[u8]>) -> Result<Header> {
@@ -233,42 +414,87 @@ pub fn decode_header(data: impl AsRef<[u8]>) -> Result<Header> {
 #[doc(hidden)]
 pub trait Reader: Sized {
     fn decode_header(&mut self) -> Result<Header>;
-    fn decode_image(&mut self, out: &mut [u8], n_encode: u32, channels: u8, src_channels: u8) -> Result<()>;
-    fn decode_islands(&mut self, n_islands: u32) -> Result<Islands>;
+    fn decode_image(&mut self, out: &mut [u8], header: &Header, channels: u8) -> Result<()>;
+    fn decode_islands(&mut self, header: &Header, skip: usize) -> Result<Islands>;
+    fn decode_canvas(&mut self, header: &Header) -> Result<Canvas>;
+    fn decode_region(
+        &mut self, out: &mut [u8], header: &Header, rect: Rect, channels: u8,
+    ) -> Result<()>;
 }
 
-struct Bytes<'a>(&'a [u8]);
+/// Reader over an encoded image held in memory, as used by [`Decoder::new`].
+///
+/// It can only be created through [`Decoder::new`]; it's public so that the decoder
+/// type, `Decoder<Bytes>`, can be named.
+pub struct Bytes<'a> {
+    data: &'a [u8],
+    /// Everything following the header, kept around for random access into the pixel stream.
+    body: &'a [u8],
+}
 
 impl<'a> Bytes<'a> {
     #[inline]
-    pub const fn new(buf: &'a [u8]) -> Self {
-        Self(buf)
+    pub(crate) const fn new(buf: &'a [u8]) -> Self {
+        Self { data: buf, body: buf }
     }
 
     #[inline]
-    pub const fn as_slice(&self) -> &[u8] {
-        self.0
+    pub(crate) const fn as_slice(&self) -> &[u8] {
+        self.data
     }
 }
 
 impl<'a> Reader for Bytes<'a> {
     #[inline]
     fn decode_header(&mut self) -> Result<Header> {
-        let header = Header::decode(self.0)?;
-        self.0 = &self.0[QOI_HEADER_SIZE..]; // can't panic
+        let header = Header::decode(self.data)?;
+        self.data = &self.data[QOI_HEADER_SIZE..]; // can't panic
+        self.body = self.data;
         Ok(header)
     }
 
     #[inline]
-    fn decode_image(&mut self, out: &mut [u8], n_encode: u32, channels: u8, src_channels: u8) -> Result<()> {
-        let n_read = decode_impl_slice_all(&self.0, out, n_encode, channels, src_channels)?;
-        self.0 = &self.0[n_read..];
+    fn decode_image(&mut self, out: &mut [u8], header: &Header, channels: u8) -> Result<()> {
+        let n_encode = header.n_encode();
+        // the island table and the padding must follow the stream
+        let min_len = header.extension_offset().saturating_add(QOI_PADDING_SIZE);
+        if unlikely(self.data.len() < min_len) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let (stream, tail) = self.data.split_at(n_encode);
+        if header.is_sparse() {
+            let rect = Rect::new(0, 0, header.width, header.height);
+            decode_sparse(self.body, out, header, rect, channels)?;
+        } else {
+            let _ = decode_impl_slice_all(stream, out, channels, header.channels.as_u8())?;
+        }
+        self.data = tail;
         Ok(())
     }
 
     #[inline]
-    fn decode_islands(&mut self, n_islands: u32) -> Result<Islands> {
-        Islands::decode(self.0, n_islands)
+    fn decode_islands(&mut self, header: &Header, skip: usize) -> Result<Islands> {
+        let data = self.data.get(skip..).ok_or(Error::UnexpectedBufferEnd)?;
+        Islands::decode(data, header)
+    }
+
+    #[inline]
+    fn decode_canvas(&mut self, header: &Header) -> Result<Canvas> {
+        let offset = header.extension_offset().saturating_add(QOI_EXTENSION_SIZE);
+        let data = self.body.get(offset..).ok_or(Error::UnexpectedBufferEnd)?;
+        Canvas::decode(data, header)
+    }
+
+    #[inline]
+    fn decode_region(
+        &mut self, out: &mut [u8], header: &Header, rect: Rect, channels: u8,
+    ) -> Result<()> {
+        if header.is_sparse() {
+            return decode_sparse(self.body, out, header, rect, channels);
+        }
+        let stream = self.body.get(..header.n_encode()).ok_or(Error::UnexpectedBufferEnd)?;
+        let width = header.width as usize;
+        decode_region_all(stream, out, width, rect, channels, header.channels.as_u8())
     }
 }
 
@@ -293,6 +519,10 @@ pub struct Decoder<R> {
     reader: R,
     header: Header,
     channels: Channels,
+    /// Number of pixel stream bytes between the reader and the island table.
+    stream_len: usize,
+    canvas: Option<Canvas>,
+    restore_canvas: bool,
 }
 
 impl<'a> Decoder<Bytes<'a>> {
@@ -313,6 +543,87 @@ impl<'a> Decoder<Bytes<'a>> {
     pub const fn data(&self) -> &[u8] {
         self.reader.as_slice()
     }
+
+    /// Returns the background colour (RGBA) if the image is stored in sparse mode.
+    #[inline]
+    pub fn background(&self) -> Option<[u8; 4]> {
+        let background = self.reader.body.get(..4).filter(|_| self.header.is_sparse())?;
+        Some([background[0], background[1], background[2], background[3]])
+    }
+
+    /// Returns the island table along with all of the sections following it.
+    #[inline]
+    fn island_table(&self) -> Result<&'a [u8]> {
+        let offset = self.header.island_table_offset();
+        self.reader.body.get(offset..).ok_or(Error::UnexpectedBufferEnd)
+    }
+
+    /// Decodes the bounding box of the `index`-th island to a pre-allocated buffer and
+    /// returns the number of bytes written.
+    ///
+    /// If the image was encoded with per-island sub-streams, only the island's own
+    /// sub-stream is decoded; otherwise, this falls back to [`Decoder::decode_region`].
+    /// The minimum size of the buffer is the [`Decoder::required_region_len`] of the
+    /// island's bounding box.
+    #[inline]
+    pub fn decode_island(&mut self, index: usize, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        let table = self.island_table()?;
+        let (rect, stream) = decode_island_record(table, &self.header, index)?;
+        let stream = match stream {
+            Some(stream) => &table[stream],
+            None => return self.decode_region(rect, buf),
+        };
+        let buf = buf.as_mut();
+        let size = self.required_region_len(rect);
+        if unlikely(buf.len() < size) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
+        }
+        let (channels, src_channels) = (self.channels.as_u8(), self.header.channels.as_u8());
+        let _ = decode_impl_slice_all(stream, &mut buf[..size], channels, src_channels)?;
+        Ok(size)
+    }
+
+    /// Decodes the bounding box of the `index`-th island into a newly allocated vector.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn decode_island_to_vec(&mut self, index: usize) -> Result<Vec<u8>> {
+        let (rect, _) = decode_island_record(self.island_table()?, &self.header, index)?;
+        let mut out = vec![0; self.required_region_len(rect)];
+        let _ = self.decode_island(index, &mut out)?;
+        Ok(out)
+    }
+
+    /// Decodes the bounding boxes of all islands, one vector of bytes per island.
+    ///
+    /// If the image was encoded with per-island sub-streams, the islands are decoded
+    /// independently and in parallel; otherwise, the image is decoded once and cropped.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    pub fn decode_all_islands(&mut self) -> Result<Vec<Vec<u8>>> {
+        let (header, table) = (self.header, self.island_table()?);
+        let (channels, src_channels) = (self.channels.as_u8(), header.channels.as_u8());
+        if header.has_island_streams() {
+            return (0..header.n_islands())
+                .into_par_iter()
+                .map(|index| {
+                    let (rect, stream) = decode_island_record(table, &header, index)?;
+                    let stream = &table[stream.unwrap_or_default()];
+                    let mut out = vec![0; rect.area() * channels as usize];
+                    let _ = decode_impl_slice_all(stream, &mut out, channels, src_channels)?;
+                    Ok(out)
+                })
+                .collect();
+        }
+        // only the stored image is decoded here, even if the canvas is to be restored
+        let mut image = vec![0; header.n_pixels() * channels as usize];
+        let stream = self.reader.body.get(..header.n_encode()).ok_or(Error::UnexpectedBufferEnd)?;
+        let _ = decode_impl_slice_all(stream, &mut image, channels, src_channels)?;
+        (0..header.n_islands())
+            .map(|index| {
+                let (rect, _) = decode_island_record(table, &header, index)?;
+                Ok(crop(&image, header.width, channels as usize, rect))
+            })
+            .collect()
+    }
 }
 
 #[cfg(feature = "std")]
@@ -334,7 +645,15 @@ impl<R: Reader> Decoder<R> {
     #[inline]
     fn new_impl(mut reader: R) -> Result<Self> {
         let header = reader.decode_header()?;
-        Ok(Self { reader, header, channels: header.channels })
+        let canvas = if header.is_trimmed() { Some(reader.decode_canvas(&header)?) } else { None };
+        Ok(Self {
+            reader,
+            header,
+            channels: header.channels,
+            stream_len: header.n_encode(),
+            canvas,
+            restore_canvas: false,
+        })
     }
 
     /// Returns a new decoder with modified number of channels.
@@ -349,6 +668,27 @@ impl<R: Reader> Decoder<R> {
         self
     }
 
+    /// Returns a new decoder that restores the full canvas of trimmed images.
+    ///
+    /// If the image was trimmed by the encoder (see
+    /// [`Encoder::with_trim`](crate::Encoder::with_trim)), the decoded image is placed
+    /// on its original canvas and surrounded with transparent (all-zero) pixels;
+    /// otherwise, this has no effect. Only decoding the whole image is affected:
+    /// regions and islands are still relative to the stored crop, use
+    /// [`Decoder::canvas`] to map them onto the canvas.
+    #[inline]
+    #[must_use]
+    pub const fn with_restore_canvas(mut self, restore: bool) -> Self {
+        self.restore_canvas = restore;
+        self
+    }
+
+    /// Returns the original canvas if the image was trimmed by the encoder.
+    #[inline]
+    pub const fn canvas(&self) -> Option<Canvas> {
+        self.canvas
+    }
+
     /// Returns the number of channels in the decoded image.
     ///
     /// Note: this may differ from the number of channels specified in the header.
@@ -368,7 +708,13 @@ impl<R: Reader> Decoder<R> {
     /// Can be used to pre-allocate the buffer to decode the image into.
     #[inline]
     pub fn required_buf_len(&self) -> usize {
-        self.header.n_pixels().saturating_mul(self.channels.as_u8() as usize)
+        let n_pixels = match self.canvas {
+            Some(canvas) if self.restore_canvas => {
+                (canvas.width as usize).saturating_mul(canvas.height as usize)
+            }
+            _ => self.header.n_pixels(),
+        };
+        n_pixels.saturating_mul(self.channels.as_u8() as usize)
     }
 
     /// Decodes the image to a pre-allocated buffer and returns the number of bytes written.
@@ -381,7 +727,16 @@ impl<R: Reader> Decoder<R> {
         if unlikely(buf.len() < size) {
             return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
         }
-        self.reader.decode_image(buf, self.header.n_encode() as u32, self.channels.as_u8(), self.header.channels.as_u8())?;
+        let channels = self.channels.as_u8();
+        match self.canvas.filter(|_| self.restore_canvas) {
+            Some(canvas) => {
+                let image_len = self.header.n_pixels() * channels as usize;
+                self.reader.decode_image(&mut buf[..image_len], &self.header, channels)?;
+                restore_canvas(&mut buf[..size], &self.header, &canvas, channels as usize);
+            }
+            None => self.reader.decode_image(buf, &self.header, channels)?,
+        }
+        self.stream_len = 0;
         Ok(size)
     }
 
@@ -389,16 +744,78 @@ impl<R: Reader> Decoder<R> {
     #[cfg(any(feature = "std", feature = "alloc"))]
     #[inline]
     pub fn decode_to_vec(&mut self) -> Result<Vec<u8>> {
-        let mut out = vec![0; self.header.n_pixels() * self.channels.as_u8() as usize];
+        let mut out = vec![0; self.required_buf_len()];
         let _ = self.decode_to_buf(&mut out)?;
         Ok(out)
     }
 
-    /// Decodes the image into a newly allocated vector of bytes and returns it.
+    /// The number of bytes the decoded region will take.
+    #[inline]
+    pub fn required_region_len(&self, rect: Rect) -> usize {
+        rect.area().saturating_mul(self.channels.as_u8() as usize)
+    }
+
+    /// Decodes a rectangular region of the image to a pre-allocated buffer and
+    /// returns the number of bytes written.
+    ///
+    /// The pixel stream is only decoded up to the last pixel of the region, and the
+    /// pixels inside the region are written tightly packed, row by row. This doesn't
+    /// consume the stream, so several regions can be decoded one after another; the
+    /// minimum size of the buffer can be found via [`Decoder::required_region_len`].
+    #[inline]
+    pub fn decode_region(&mut self, rect: Rect, mut buf: impl AsMut<[u8]>) -> Result<usize> {
+        validate_region(rect, &self.header)?;
+        let buf = buf.as_mut();
+        let size = self.required_region_len(rect);
+        if unlikely(buf.len() < size) {
+            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size });
+        }
+        self.reader.decode_region(&mut buf[..size], &self.header, rect, self.channels.as_u8())?;
+        Ok(size)
+    }
+
+    /// Decodes a rectangular region of the image into a newly allocated vector of bytes.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn decode_region_to_vec(&mut self, rect: Rect) -> Result<Vec<u8>> {
+        let mut out = vec![0; self.required_region_len(rect)];
+        let _ = self.decode_region(rect, &mut out)?;
+        Ok(out)
+    }
+
+    /// Decodes the island table that follows the pixel stream.
+    ///
+    /// This can be called either before or after decoding the image; in the former
+    /// case, the pixel stream is skipped without being decoded.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn islands(&mut self) -> Result<Islands> {
+        self.reader.decode_islands(&self.header, self.stream_len)
+    }
+
+    /// Decodes the island table that follows the pixel stream.
+    #[deprecated(note = "use `Decoder::islands` instead")]
     #[cfg(any(feature = "std", feature = "alloc"))]
     #[inline]
     pub fn decode_to_islands(&mut self) -> Result<Islands> {
-        let islands = self.reader.decode_islands(self.header.n_islands() as u32)?;
-        Ok(islands)
+        self.islands()
+    }
+
+    /// Decodes the foreground masks of the islands, one per island and in the same order.
+    ///
+    /// Returns an empty vector if the image was encoded without island masks.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn island_masks(&mut self) -> Result<Vec<IslandMask>> {
+        Ok(self.islands()?.masks)
+    }
+
+    /// Decodes the contours of the islands, one list per island and in the same order.
+    ///
+    /// Returns an empty vector if the image was encoded without island contours.
+    #[cfg(any(feature = "std", feature = "alloc"))]
+    #[inline]
+    pub fn island_contours(&mut self) -> Result<Vec<Vec<Contour>>> {
+        Ok(self.islands()?.contours)
     }
 }
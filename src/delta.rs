@@ -0,0 +1,134 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use bytemuck::cast_slice;
+use rayon::prelude::*;
+
+use crate::consts::{QOI_DELTA_HEADER_SIZE, QOI_DELTA_MAGIC};
+use crate::decode::Decoder;
+use crate::encode::Encoder;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::island::{IslandOptions, Islands};
+use crate::rect::Rect;
+use crate::stream::{crop, paste};
+use crate::types::{Channels, ColorSpace};
+use crate::utils::unlikely;
+
+/// Size of a single patch record in a delta: the rectangle and the length of its image.
+const DELTA_RECORD_SIZE: usize = 20;
+
+/// Encodes the changes between two frames as a delta.
+///
+/// The delta starts with a header (magic, frame dimensions, number of channels and
+/// number of patches), followed by one record per patch (rectangle and encoded length)
+/// and the patches themselves, each being a complete QOI image of the changed pixels.
+#[allow(clippy::cast_possible_truncation)]
+pub fn encode_delta(
+    prev: &[u8], next: &[u8], header: &Header, options: &IslandOptions,
+) -> Result<Vec<u8>> {
+    // only the rectangles matter, don't compute anything else
+    let options =
+        options.clone().with_stats(false).with_masks(false).with_contours(false).with_labels(false);
+    let islands =
+        Islands::diff(prev, next, header.width, header.height, header.channels, &options)?;
+    let channels = header.channels.as_u8() as usize;
+    let patches = islands
+        .islands
+        .par_iter()
+        .map(|island| {
+            let Rect { width, height, .. } = island.rect;
+            let pixels = crop(next, header.width, channels, island.rect);
+            Encoder::new(&pixels, width, height)?
+                .with_colorspace(header.colorspace)
+                .encode_to_vec(false)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let size = QOI_DELTA_HEADER_SIZE
+        + patches.iter().map(|patch| DELTA_RECORD_SIZE + patch.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&QOI_DELTA_MAGIC.to_be_bytes());
+    out.extend_from_slice(&header.width.to_be_bytes());
+    out.extend_from_slice(&header.height.to_be_bytes());
+    out.push(header.channels.as_u8());
+    out.extend_from_slice(&(patches.len() as u32).to_be_bytes());
+    for (island, patch) in islands.iter().zip(&patches) {
+        let rect = island.rect;
+        for v in &[rect.x, rect.y, rect.width, rect.height, patch.len() as u32] {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+    for patch in &patches {
+        out.extend_from_slice(patch);
+    }
+    Ok(out)
+}
+
+/// Applies a delta produced by [`Encoder::encode_delta_to_vec`] in place onto a frame
+/// and returns the rectangles that were updated.
+///
+/// The frame must be a row-major array of raw pixels with the dimensions and the
+/// number of channels of the frames the delta was computed from. The delta is fully
+/// decoded and validated before the frame is modified, so the frame is left intact
+/// if an error is returned.
+pub fn apply_delta(delta: impl AsRef<[u8]>, frame: &mut [u8]) -> Result<Vec<Rect>> {
+    let data = delta.as_ref();
+    if unlikely(data.len() < QOI_DELTA_HEADER_SIZE) {
+        return Err(Error::UnexpectedBufferEnd);
+    }
+    let v = cast_slice::<_, [u8; 4]>(&data[..12]);
+    let magic = u32::from_be_bytes(v[0]);
+    if unlikely(magic != QOI_DELTA_MAGIC) {
+        return Err(Error::InvalidDeltaMagic { magic });
+    }
+    let (width, height) = (u32::from_be_bytes(v[1]), u32::from_be_bytes(v[2]));
+    let channels = Channels::try_from(data[12])?;
+    let header = Header::try_new(width, height, 0, 0, channels, ColorSpace::default())?;
+    if unlikely(frame.len() != header.n_bytes()) {
+        return Err(Error::InvalidImageLength { size: frame.len(), width, height });
+    }
+    let n_patches = u32::from_be_bytes([data[13], data[14], data[15], data[16]]) as usize;
+
+    let body = &data[QOI_DELTA_HEADER_SIZE..];
+    let records_len = n_patches.saturating_mul(DELTA_RECORD_SIZE);
+    if unlikely(body.len() < records_len) {
+        return Err(Error::UnexpectedBufferEnd);
+    }
+    let (records, mut payload) = body.split_at(records_len);
+    let mut patches = Vec::with_capacity(n_patches);
+    for record in records.chunks_exact(DELTA_RECORD_SIZE) {
+        let v = cast_slice::<_, [u8; 4]>(record);
+        let field = |i: usize| u32::from_be_bytes(v[i]);
+        let (x, y, len) = (field(0), field(1), field(4));
+        let rect = Rect::new(x, y, field(2), field(3));
+        if unlikely(rect.is_empty() || rect.clip_to(&header) != Some(rect)) {
+            return Err(Error::InvalidRegion { x, y, width: rect.width, height: rect.height });
+        }
+        if unlikely(payload.len() < len as usize) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let (patch, tail) = payload.split_at(len as usize);
+        patches.push((rect, patch));
+        payload = tail;
+    }
+
+    let decoded = patches
+        .par_iter()
+        .map(|&(rect, patch)| {
+            let mut decoder = Decoder::new(patch)?.with_channels(channels);
+            let Header { width, height, .. } = *decoder.header();
+            if unlikely((width, height) != (rect.width, rect.height)) {
+                let Rect { x, y, width, height } = rect;
+                return Err(Error::InvalidRegion { x, y, width, height });
+            }
+            decoder.decode_to_vec()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let channels = channels.as_u8() as usize;
+    for (&(rect, _), pixels) in patches.iter().zip(&decoded) {
+        paste(frame, width, channels, rect, pixels);
+    }
+    Ok(patches.into_iter().map(|(rect, _)| rect).collect())
+}
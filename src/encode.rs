@@ -1,74 +1,47 @@
 #[cfg(any(feature = "std", feature = "alloc"))]
 use alloc::{vec, vec::Vec};
 use core::convert::TryFrom;
-use std::collections::HashSet;
-use bytemuck::Pod;
-use rayon::prelude::*;
-
-
 
+use bytemuck::Pod;
 
-use crate::consts::{QOI_HEADER_SIZE, QOI_OP_INDEX, QOI_OP_RUN, QOI_PADDING, QOI_PADDING_SIZE};
+use crate::consts::{
+    QOI_CANVAS_SIZE, QOI_EXTENSION_SIZE, QOI_FLAG_ISLAND_IDS, QOI_FLAG_ISLAND_STREAMS,
+    QOI_FLAG_SPARSE, QOI_FLAG_TRIMMED, QOI_HEADER_SIZE, QOI_OP_INDEX, QOI_OP_RUN, QOI_PADDING,
+    QOI_PADDING_SIZE,
+};
+use crate::delta::encode_delta;
 use crate::error::{Error, Result};
-use crate::header::Header;
-use crate::Island;
-use crate::island::{Islands, Point};
+use crate::header::{Canvas, Header};
+use crate::island::{Connectivity, Foreground, IslandOptions, Islands, Trim};
 use crate::pixel::{Pixel, SupportedChannels};
+use crate::rect::Rect;
+use crate::stream::{crop, encode_island_streams};
+use crate::tracker::{IslandEvent, IslandTracker};
 use crate::types::{Channels, ColorSpace};
 
 use crate::utils::{unlikely, BytesMut, Writer};
 
 #[allow(clippy::cast_possible_truncation, unused_assignments, unused_variables)]
-fn encode_impl<W: Writer, const N: usize>(mut buf: W, data: &[u8], header: &Header, encode_islands:bool) -> Result<(usize, usize)>
+fn encode_stream<W: Writer, const N: usize>(mut buf: W, data: &[u8]) -> Result<W>
 where
     Pixel<N>: SupportedChannels,
     [u8; N]: Pod,
 {
-    let cap = buf.capacity();
-
     let mut index = [Pixel::new(); 256];
     let mut px_prev = Pixel::new().with_a(0xff);
     let mut hash_prev = px_prev.hash_index();
     let mut run = 0_u8;
     let mut px = Pixel::<N>::new().with_a(0xff);
-    // let mut zero_px = Pixel::<N>::new();
-    // zero_px.update_rgba(255, 255, 255, 0);
-    let zero_px = Pixel::<N>::new();
     let mut index_allowed = false;
 
     let n_pixels = data.len() / N;
 
-    let threads = 8;
-    let mut points: Vec<HashSet<Point>> = (0 .. threads)
-      .map(|_| HashSet::<Point>::new())
-      .collect();
-
-    let mut row = 0;
-    let mut col = 0;
-    //
-    // // let mut image_encoding_buf = BytesMut::new(vec![0_u8; cap].as_mut());\
-    // let mut image_encoding_vec = vec![0_u8; cap];
-    // let mut image_encoding_buf = BytesMut::new(image_encoding_vec.as_mut());
-
     for (i, chunk) in data.chunks_exact(N).enumerate() {
-
-        col = i - (row * header.width) as usize;
-        if col >= header.width as usize {
-            row += 1;
-            col = 0;
-        }
-
         px.read(chunk);
-
-        if px != zero_px {
-            points[(row % threads) as usize].insert((row, col as u32));
-        }
-
         if px == px_prev {
             run += 1;
             if run == 62 || unlikely(i == n_pixels - 1) {
                 buf = buf.write_one(QOI_OP_RUN | (run - 1))?;
-                // image_encoding_buf = image_encoding_buf.write_one(QOI_OP_RUN | (run - 1));
                 run = 0;
             }
         } else {
@@ -85,7 +58,6 @@ where
                 #[cfg(feature = "reference")]
                 {
                     buf = buf.write_one(QOI_OP_RUN | (run - 1))?;
-                    // image_encoding_buf = image_encoding_buf.write_one(QOI_OP_RUN | (run - 1));
                 }
                 run = 0;
             }
@@ -95,48 +67,47 @@ where
             let index_px = &mut index[hash_prev as usize];
             if *index_px == px_rgba {
                 buf = buf.write_one(QOI_OP_INDEX | hash_prev)?;
-                // image_encoding_buf = image_encoding_buf.write_one(QOI_OP_INDEX | hash_prev);
             } else {
                 *index_px = px_rgba;
                 buf = px.encode_into(px_prev, buf)?;
-                // image_encoding_buf = px.encode_into(px_prev, image_encoding_buf)?;
             }
             px_prev = px;
         }
     }
 
-    let mut islands: Vec<Island> = vec![];
-    if encode_islands {
-        islands = points.par_iter()
-          .map(|x| Islands::find_islands(x))
-          .reduce(|| Vec::new(),
-                  |mut acc, itr| {
-                      acc.extend(itr);
-                      acc
-                  }
-          );
-    }
-
-    // let islands = Islands::find_islands(&points, header.width, header.height)?;
-    buf = Islands::encode(buf, &islands)?;
-    // buf = buf.write_many(image_encoding_vec.as_mut())?;
-    buf = buf.write_many(&QOI_PADDING)?;
-
-    // println!("number of islands:{}", islands.islands.len());
-    Ok((cap.saturating_sub(buf.capacity()), islands.len()))
+    Ok(buf)
 }
 
+/// Encodes the pixels as a bare QOI stream (no header, no padding).
 #[inline]
-fn encode_impl_all<W: Writer>(out: W, data: &[u8], header: &Header, encode_islands: bool) -> Result<(usize, usize)> {
-    match header.channels {
-        Channels::Rgb => encode_impl::<_, 3>(out, data, header, encode_islands),
-        Channels::Rgba => encode_impl::<_, 4>(out, data, header, encode_islands),
+pub fn encode_stream_all<W: Writer>(out: W, data: &[u8], channels: Channels) -> Result<W> {
+    match channels {
+        Channels::Rgb => encode_stream::<_, 3>(out, data),
+        Channels::Rgba => encode_stream::<_, 4>(out, data),
     }
 }
 
+/// Encodes the pixel stream, or the background colour in sparse mode; returns the
+/// number of bytes written.
+#[inline]
+fn encode_pixels_all<W: Writer>(
+    buf: W, data: &[u8], channels: Channels, background: Option<[u8; 4]>,
+) -> Result<usize> {
+    let cap = buf.capacity();
+    let buf = match background {
+        Some(background) => buf.write_many(&background)?,
+        None => encode_stream_all(buf, data, channels)?,
+    };
+    Ok(cap.saturating_sub(buf.capacity()))
+}
+
 /// The maximum number of bytes the encoded image will take.
 ///
-/// Can be used to pre-allocate the buffer to encode the image into.
+/// Can be used to pre-allocate the buffer to encode the image into. The island table
+/// isn't included: it's written after the pixel stream, which almost never takes its
+/// maximum size, so a buffer of this size usually fits the islands as well. If it
+/// doesn't, encoding fails with [`Error::OutputBufferTooSmall`] holding the exact
+/// number of bytes required.
 #[inline]
 pub fn encode_max_len(width: u32, height: u32, channels: impl Into<u8>) -> usize {
     let (width, height) = (width as usize, height as usize);
@@ -149,7 +120,8 @@ pub fn encode_max_len(width: u32, height: u32, channels: impl Into<u8>) -> usize
 
 /// Encode the image into a pre-allocated buffer.
 ///
-/// Returns the total number of bytes written.
+/// Returns the total number of bytes written. The buffer can be sized via
+/// [`encode_max_len`], see there for the space taken by the islands.
 #[inline]
 pub fn encode_to_buf(
     buf: impl AsMut<[u8]>, data: impl AsRef<[u8]>, width: u32, height: u32, encode_islands: bool
@@ -177,6 +149,10 @@ pub fn encode_to_vec(data: impl AsRef<[u8]>, width: u32, height: u32, encode_isl
 pub struct Encoder<'a> {
     data: &'a [u8],
     header: Header,
+    island_options: IslandOptions,
+    background: Option<[u8; 4]>,
+    trim: Option<Trim>,
+    canvas: Option<Canvas>,
 }
 
 impl<'a> Encoder<'a> {
@@ -196,7 +172,8 @@ impl<'a> Encoder<'a> {
             return Err(Error::InvalidImageLength { size, width, height });
         }
         header.channels = Channels::try_from(n_channels.min(0xff) as u8)?;
-        Ok(Self { data, header })
+        let island_options = IslandOptions::default();
+        Ok(Self { data, header, island_options, background: None, trim: None, canvas: None })
     }
 
     /// Returns a new encoder with modified color space.
@@ -209,6 +186,66 @@ impl<'a> Encoder<'a> {
         self
     }
 
+    /// Returns a new encoder with modified island connectivity.
+    ///
+    /// By default, only orthogonal neighbours are connected; use [`Connectivity::Eight`]
+    /// to keep diagonal strokes in a single island.
+    #[inline]
+    #[must_use]
+    pub fn with_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.island_options = self.island_options.with_connectivity(connectivity);
+        self
+    }
+
+    /// Returns a new encoder with modified island detection options.
+    ///
+    /// The options decide which pixels are considered foreground and how they are
+    /// grouped into islands; they only matter when islands are encoded.
+    #[inline]
+    #[must_use]
+    pub fn with_island_options(mut self, options: IslandOptions) -> Self {
+        self.island_options = options;
+        self
+    }
+
+    /// Returns a new encoder that only stores the islands on top of a uniform background.
+    ///
+    /// Instead of the full pixel stream, only the background colour (RGBA; the alpha
+    /// is ignored for RGB images) and an independent sub-stream per island bounding box
+    /// are stored; the decoder fills the canvas with the background and pastes the islands
+    /// on top. This is much more compact for mostly empty layers.
+    ///
+    /// To keep the encoding lossless, the islands are made of every pixel that differs
+    /// from the background colour: the foreground predicate, the minimum area and the
+    /// island cap of the island options are ignored in this mode.
+    ///
+    /// Islands are always encoded in this mode, regardless of the `encode_islands` flag.
+    #[inline]
+    #[must_use]
+    pub const fn with_sparse(mut self, background: [u8; 4]) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    /// Returns a new encoder that only stores a crop of the image.
+    ///
+    /// The image is cropped to the union of the island bounding boxes, or to the bounding
+    /// box of all foreground pixels, and the original canvas size along with the position
+    /// of the crop is stored as a [`Canvas`]; the decoder can restore the full canvas with
+    /// a transparent fill (see
+    /// [`Decoder::with_restore_canvas`](crate::Decoder::with_restore_canvas)). An image
+    /// without any foreground is cropped to its top-left pixel.
+    ///
+    /// The header and the islands of the encoded image describe the crop, so island
+    /// rectangles and centroids are relative to its top-left corner; after encoding,
+    /// [`Encoder::header`] describes the crop as well. Frame deltas are never trimmed.
+    #[inline]
+    #[must_use]
+    pub const fn with_trim(mut self, trim: Trim) -> Self {
+        self.trim = Some(trim);
+        self
+    }
+
     /// Returns the inferred number of channels.
     #[inline]
     pub const fn channels(&self) -> Channels {
@@ -221,42 +258,268 @@ impl<'a> Encoder<'a> {
         &self.header
     }
 
+    /// Returns the header of the whole image, which differs from [`Encoder::header`]
+    /// once a trimmed image has been encoded.
+    #[inline]
+    const fn image_header(&self) -> Header {
+        match self.canvas {
+            Some(canvas) if self.trim.is_some() => {
+                Header { width: canvas.width, height: canvas.height, ..self.header }
+            }
+            _ => self.header,
+        }
+    }
+
     /// The maximum number of bytes the encoded image will take.
     ///
-    /// Can be used to pre-allocate the buffer to encode the image into.
+    /// Can be used to pre-allocate the buffer to encode the image into; like
+    /// [`encode_max_len`], this doesn't include the island table, which usually fits
+    /// in the space left over by the pixel stream.
     #[inline]
     pub fn required_buf_len(&self) -> usize {
-        self.header.encode_max_len()
+        let canvas_len = if self.trim.is_some() { QOI_EXTENSION_SIZE + QOI_CANVAS_SIZE } else { 0 };
+        self.image_header().encode_max_len() + canvas_len
     }
 
     /// Encodes the image to a pre-allocated buffer and returns the number of bytes written.
     ///
     /// The minimum size of the buffer can be found via [`Encoder::required_buf_len`].
+    /// The islands (always present in sparse mode) are written after the pixel stream;
+    /// if they don't fit in the rest of the buffer, [`Error::OutputBufferTooSmall`] is
+    /// returned with the exact number of bytes required.
     #[inline]
-    pub fn encode_to_buf(&mut self, mut buf: impl AsMut<[u8]>, encode_islands:bool) -> Result<usize> {
+    pub fn encode_to_buf(&mut self, mut buf: impl AsMut<[u8]>, encode_islands: bool) -> Result<usize> {
         let buf = buf.as_mut();
-        let size_required = self.required_buf_len();
-        if unlikely(buf.len() < size_required) {
-            return Err(Error::OutputBufferTooSmall { size: buf.len(), required: size_required });
+        let trimmed = self.with_trimmed(|encoder| encoder.encode_to_buf(&mut *buf, encode_islands));
+        if let Some(result) = trimmed {
+            return result;
+        }
+        let islands = self.find_islands(encode_islands);
+        let streams = self.encode_island_streams(&islands)?;
+        self.encode_islands_to_buf(buf, &islands, &streams)
+    }
+
+    /// Crops the image as requested by [`Encoder::with_trim`] and runs `f` on an encoder
+    /// of the crop, taking over its header afterwards; returns `None` if the image isn't
+    /// trimmed.
+    #[allow(clippy::cast_possible_truncation)]
+    fn with_trimmed<T>(
+        &mut self, f: impl FnOnce(&mut Encoder<'_>) -> Result<T>,
+    ) -> Option<Result<T>> {
+        let trim = self.trim?;
+        let header = self.image_header();
+        let (width, height) = (header.width, header.height);
+        let channels = header.channels.as_u8() as usize;
+        let rect = match trim {
+            Trim::Islands => {
+                let options = self.island_options.clone().with_stats(false).with_masks(false);
+                let options = options.with_contours(false).with_labels(false);
+                let islands = Islands::detect_impl(self.data, &header, &options);
+                islands.iter().fold(Rect::default(), |acc, island| acc.union(&island.rect))
+            }
+            Trim::Foreground => {
+                let foreground = self.island_options.foreground();
+                let mask = match header.channels {
+                    Channels::Rgb => foreground.mask::<3>(self.data),
+                    Channels::Rgba => foreground.mask::<4>(self.data),
+                };
+                let mut rect = Rect::default();
+                for (y, row) in mask.chunks_exact(width as usize).enumerate() {
+                    let x0 = row.iter().position(|&fg| fg);
+                    let x1 = row.iter().rposition(|&fg| fg);
+                    if let (Some(x0), Some(x1)) = (x0, x1) {
+                        let span = Rect::new(x0 as u32, y as u32, (x1 - x0 + 1) as u32, 1);
+                        rect = rect.union(&span);
+                    }
+                }
+                rect
+            }
+        };
+        let rect = if rect.is_empty() { Rect::new(0, 0, 1, 1) } else { rect };
+        let pixels = crop(self.data, width, channels, rect);
+        let foreground = self.island_options.foreground().anchored(self.data, channels);
+        let mut encoder = Encoder {
+            data: &pixels,
+            header: Header { width: rect.width, height: rect.height, ..header },
+            island_options: self.island_options.clone().with_foreground(foreground),
+            background: self.background,
+            trim: None,
+            canvas: Some(Canvas { width, height, x: rect.x, y: rect.y }),
+        };
+        let result = f(&mut encoder);
+        self.header = encoder.header;
+        self.canvas = encoder.canvas;
+        Some(result)
+    }
+
+    /// Returns the header flags that don't depend on the island pass.
+    #[inline]
+    const fn mode_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.background.is_some() {
+            flags |= QOI_FLAG_ISLAND_STREAMS | QOI_FLAG_SPARSE;
+        }
+        if self.canvas.is_some() {
+            flags |= QOI_FLAG_TRIMMED;
+        }
+        flags
+    }
+
+    /// Runs the island pass (if requested) and updates the header flags accordingly.
+    #[inline]
+    fn find_islands(&mut self, encode_islands: bool) -> Islands {
+        self.header.flags = self.mode_flags();
+        if !encode_islands && self.background.is_none() {
+            return Islands::default();
+        }
+        self.header.flags |= self.island_options.flags();
+        if let Some(color) = self.background {
+            // whatever isn't covered by an island is replaced by the background
+            let options = self.island_options.clone();
+            let options = options.with_foreground(Foreground::ChromaKey { color, tolerance: 0 });
+            let options = options.with_min_area(0).with_max_islands(None);
+            return Islands::detect_impl(self.data, &self.header, &options);
+        }
+        Islands::detect_impl(self.data, &self.header, &self.island_options)
+    }
+
+    /// Encodes the per-island sub-streams if they were requested.
+    #[inline]
+    fn encode_island_streams(&self, islands: &Islands) -> Result<Vec<Vec<u8>>> {
+        if !self.header.has_island_streams() {
+            return Ok(Vec::new());
+        }
+        encode_island_streams(self.data, &self.header, islands.as_slice())
+    }
+
+    /// The maximum number of bytes the encoded image will take without its islands.
+    #[inline]
+    fn image_max_len(&self) -> usize {
+        if self.header.is_sparse() {
+            QOI_HEADER_SIZE + 4 + QOI_PADDING_SIZE
+        } else {
+            self.header.encode_max_len()
+        }
+    }
+
+    /// The maximum number of bytes the encoded image will take along with its islands.
+    #[inline]
+    fn required_buf_len_with(&self, islands: &Islands, streams: &[Vec<u8>]) -> usize {
+        self.image_max_len() + islands.encoded_len(self.header.flags, streams)
+    }
+
+    /// Encodes the pixel stream (or the background colour, in sparse mode) followed by
+    /// the island table, the extension section (holding the canvas, if trimmed) and the
+    /// padding; returns the total number of bytes written.
+    ///
+    /// The buffer must fit the pixel stream in the worst case; the islands are only
+    /// checked against the space actually left once the stream is written.
+    #[inline]
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode_islands_to_buf(
+        &mut self, buf: &mut [u8], islands: &Islands, streams: &[Vec<u8>],
+    ) -> Result<usize> {
+        let (size, islands_len) = (buf.len(), islands.encoded_len(self.header.flags, streams));
+        if unlikely(size < self.image_max_len()) {
+            let required = self.image_max_len() + islands_len;
+            return Err(Error::OutputBufferTooSmall { size, required });
         }
         let (head, tail) = buf.split_at_mut(QOI_HEADER_SIZE); // can't panic
-        let (n_encode, n_islands) = encode_impl_all(BytesMut::new(tail), self.data, &self.header, encode_islands)?;
+        let out = BytesMut::new(&mut *tail);
+        let n_encode = encode_pixels_all(out, self.data, self.header.channels, self.background)?;
+        let required = QOI_HEADER_SIZE + n_encode + islands_len + QOI_PADDING_SIZE;
+        if unlikely(size < required) {
+            return Err(Error::OutputBufferTooSmall { size, required });
+        }
+        let out = BytesMut::new(&mut tail[n_encode..]);
+        let out = islands.encode(out, self.header.flags, streams, self.canvas.as_ref())?;
+        let out = out.write_many(&QOI_PADDING);
+        debug_assert_eq!(out.capacity(), size - required);
         self.header.n_encode = n_encode as u32;
-        self.header.n_islands = n_islands as u32;
+        self.header.n_islands = islands.len() as u32;
         head.copy_from_slice(&self.header.encode());
-        Ok(QOI_HEADER_SIZE + n_encode)
+        Ok(required)
     }
 
     /// Encodes the image into a newly allocated vector of bytes and returns it.
     #[cfg(any(feature = "alloc", feature = "std"))]
     #[inline]
     pub fn encode_to_vec(&mut self, encode_islands: bool) -> Result<Vec<u8>> {
-        let mut out = vec![0_u8; self.required_buf_len()];
-        let size = self.encode_to_buf(&mut out, encode_islands)?;
+        Ok(self.encode_to_vec_with_islands(encode_islands)?.0)
+    }
+
+    /// Same as [`Encoder::encode_to_vec`], but also returns the islands found by the
+    /// island pass, e.g. along with their label map if it's enabled in the options.
+    ///
+    /// The islands are empty if they weren't encoded.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub fn encode_to_vec_with_islands(
+        &mut self, encode_islands: bool,
+    ) -> Result<(Vec<u8>, Islands)> {
+        if let Some(result) =
+            self.with_trimmed(|encoder| encoder.encode_to_vec_with_islands(encode_islands))
+        {
+            return result;
+        }
+        let islands = self.find_islands(encode_islands);
+        Ok((self.encode_found_islands_to_vec(&islands)?, islands))
+    }
+
+    /// Encodes the image along with its islands, matching them to the islands of the
+    /// previous frame encoded with the same tracker.
+    ///
+    /// The persistent ids assigned by the tracker are stored in the island records, and
+    /// the events describing what happened to the islands since the previous frame are
+    /// returned along with the encoded image.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub fn encode_tracked_to_vec(
+        &mut self, tracker: &mut IslandTracker,
+    ) -> Result<(Vec<u8>, Vec<IslandEvent>)> {
+        if let Some(result) = self.with_trimmed(|encoder| encoder.encode_tracked_to_vec(tracker)) {
+            return result;
+        }
+        let mut islands = self.find_islands(true);
+        let events = tracker.update(&mut islands.islands);
+        self.header.flags |= QOI_FLAG_ISLAND_IDS;
+        Ok((self.encode_found_islands_to_vec(&islands)?, events))
+    }
+
+    /// Encodes the image along with the given islands instead of running the island pass.
+    ///
+    /// Only the bounding boxes and the ids of the islands are stored, on top of whatever
+    /// the sparse mode and trimming require.
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub(crate) fn encode_given_islands_to_vec(&mut self, islands: &Islands) -> Result<Vec<u8>> {
+        self.header.flags = self.mode_flags() | QOI_FLAG_ISLAND_IDS;
+        self.encode_found_islands_to_vec(islands)
+    }
+
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    fn encode_found_islands_to_vec(&mut self, islands: &Islands) -> Result<Vec<u8>> {
+        let streams = self.encode_island_streams(islands)?;
+        let mut out = vec![0_u8; self.required_buf_len_with(islands, &streams)];
+        let size = self.encode_islands_to_buf(&mut out, islands, &streams)?;
         out.truncate(size);
         Ok(out)
     }
 
+    /// Encodes the changes from a previous frame to this one as a compact delta.
+    ///
+    /// The changed pixels are grouped into islands using the island options (the
+    /// foreground predicate is ignored, see [`Islands::diff`]), and the bounding box of
+    /// each island is stored as a separate QOI image along with its rectangle. The
+    /// previous frame must have the same dimensions and number of channels; the delta
+    /// can be applied onto a copy of it via [`apply_delta`](crate::apply_delta).
+    #[cfg(any(feature = "alloc", feature = "std"))]
+    #[inline]
+    pub fn encode_delta_to_vec(&self, prev: impl AsRef<[u8]>) -> Result<Vec<u8>> {
+        encode_delta(prev.as_ref(), self.data, &self.image_header(), &self.island_options)
+    }
+
     // Encodes the image directly to a generic writer that implements [`Write`](std::io::Write).
     //
     // Note: while it's possible to pass a `&mut [u8]` slice here since it implements `Write`,
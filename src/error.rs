@@ -1,13 +1,15 @@
 use core::convert::Infallible;
 use core::fmt::{self, Display};
 
-use crate::consts::QOI_MAGIC;
+use crate::consts::{QOI_DELTA_MAGIC, QOI_MAGIC};
 
 /// Errors that can occur during encoding or decoding.
 #[derive(Debug)]
 pub enum Error {
     /// Leading 4 magic bytes don't match when decoding
     InvalidMagic { magic: u32 },
+    /// Leading 4 magic bytes of a frame delta don't match when applying it
+    InvalidDeltaMagic { magic: u32 },
     /// Invalid number of channels: expected 3 or 4
     InvalidChannels { channels: u8 },
     /// Invalid color space: expected 0 or 1
@@ -18,6 +20,18 @@ pub enum Error {
     InvalidImageLength { size: usize, width: u32, height: u32 },
     /// Output buffer is too small to fit encoded/decoded image
     OutputBufferTooSmall { size: usize, required: usize },
+    /// Region to decode is empty or doesn't fit within the image
+    InvalidRegion { x: u32, y: u32, width: u32, height: u32 },
+    /// Island index is out of range of the island table
+    InvalidIslandIndex { index: usize, n_islands: usize },
+    /// Island bounding box is empty or doesn't fit within the image
+    InvalidIsland { index: usize, x: u32, y: u32, width: u32, height: u32 },
+    /// Island table is shorter than implied by the number of islands in the header
+    TruncatedIslandTable { expected: usize, found: usize },
+    /// Images to pack don't fit into an atlas of the maximum size
+    AtlasTooSmall { width: u32, height: u32 },
+    /// Island sub-streams extend beyond the 4 GiB that the island fields can address
+    IslandStreamsTooLarge { size: usize },
     /// Input buffer ended unexpectedly before decoding was finished
     UnexpectedBufferEnd,
     /// Invalid stream end marker encountered when decoding
@@ -36,6 +50,10 @@ impl Display for Error {
             Self::InvalidMagic { magic } => {
                 write!(f, "invalid magic: expected {:?}, got {:?}", QOI_MAGIC, magic.to_be_bytes())
             }
+            Self::InvalidDeltaMagic { magic } => {
+                let (expected, magic) = (QOI_DELTA_MAGIC.to_be_bytes(), magic.to_be_bytes());
+                write!(f, "invalid delta magic: expected {:?}, got {:?}", expected, magic)
+            }
             Self::InvalidChannels { channels } => {
                 write!(f, "invalid number of channels: {}", channels)
             }
@@ -51,6 +69,24 @@ impl Display for Error {
             Self::OutputBufferTooSmall { size, required } => {
                 write!(f, "output buffer size too small: {} (required: {})", size, required)
             }
+            Self::InvalidRegion { x, y, width, height } => {
+                write!(f, "invalid region: {}x{} at ({}, {})", width, height, x, y)
+            }
+            Self::InvalidIslandIndex { index, n_islands } => {
+                write!(f, "invalid island index: {} (number of islands: {})", index, n_islands)
+            }
+            Self::InvalidIsland { index, x, y, width, height } => {
+                write!(f, "invalid island #{}: {}x{} at ({}, {})", index, width, height, x, y)
+            }
+            Self::TruncatedIslandTable { expected, found } => {
+                write!(f, "truncated island table: {} bytes (expected: {})", found, expected)
+            }
+            Self::AtlasTooSmall { width, height } => {
+                write!(f, "images don't fit into an atlas of at most {}x{}", width, height)
+            }
+            Self::IslandStreamsTooLarge { size } => {
+                write!(f, "island sub-streams too large: {} bytes (at most 4 GiB)", size)
+            }
             Self::UnexpectedBufferEnd => {
                 write!(f, "unexpected input buffer end while decoding")
             }
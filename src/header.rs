@@ -2,9 +2,15 @@ use core::convert::TryInto;
 
 use bytemuck::cast_slice;
 
-use crate::consts::{QOI_HEADER_SIZE, QOI_MAGIC, QOI_PIXELS_MAX};
+use crate::consts::{
+    QOI_CANVAS_SIZE, QOI_EXTENSION_MAGIC, QOI_EXTENSION_SIZE, QOI_FLAG_ISLAND_CONTOURS,
+    QOI_FLAG_ISLAND_IDS, QOI_FLAG_ISLAND_MASKS, QOI_FLAG_ISLAND_STATS, QOI_FLAG_ISLAND_STREAMS,
+    QOI_FLAG_SPARSE, QOI_FLAG_TRIMMED, QOI_HEADER_SIZE, QOI_ISLAND_SIZE, QOI_MAGIC,
+    QOI_PADDING, QOI_PADDING_SIZE, QOI_PIXELS_MAX,
+};
 use crate::encode_max_len;
 use crate::error::{Error, Result};
+use crate::rect::Rect;
 use crate::types::{Channels, ColorSpace};
 use crate::utils::unlikely;
 
@@ -15,12 +21,13 @@ use crate::utils::unlikely;
 /// * Both width and height must be non-zero.
 /// * Maximum number of pixels is 400Mp (=4e8 pixels).
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     /// Image width in pixels
     pub width: u32,
     /// Image height in pixels
     pub height: u32,
-    /// Number of islands
+    /// Size of the encoded pixel stream in bytes
     pub n_encode: u32,
     /// Number of islands
     pub n_islands: u32,
@@ -28,6 +35,11 @@ pub struct Header {
     pub channels: Channels,
     /// Color space (informative field, doesn't affect encoding)
     pub colorspace: ColorSpace,
+    /// Bit flags describing optional island data stored in the image
+    ///
+    /// The flags aren't part of the header itself: they're stored in an extension section
+    /// following the island table, which is only present if any of them are set.
+    pub flags: u8,
 }
 
 impl Default for Header {
@@ -40,6 +52,7 @@ impl Default for Header {
             n_islands: 0,
             channels: Channels::default(),
             colorspace: ColorSpace::default(),
+            flags: 0,
         }
     }
 }
@@ -54,7 +67,7 @@ impl Header {
         if unlikely(n_pixels == 0 || n_pixels > QOI_PIXELS_MAX) {
             return Err(Error::InvalidImageDimensions { width, height });
         }
-        Ok(Self { width, height, n_encode, n_islands, channels, colorspace })
+        Ok(Self { width, height, n_encode, n_islands, channels, colorspace, flags: 0 })
     }
 
     /// Creates a new header with modified channels.
@@ -71,6 +84,14 @@ impl Header {
         self
     }
 
+    /// Creates a new header with modified flags.
+    #[inline]
+    #[must_use]
+    pub const fn with_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
     /// Serializes the header into a bytes array.
     #[inline]
     pub(crate) fn encode(&self) -> [u8; QOI_HEADER_SIZE] {
@@ -86,7 +107,12 @@ impl Header {
     }
 
     /// Deserializes the header from a byte array.
+    ///
+    /// If `data` holds the whole image, the flags are read from the extension section;
+    /// images written before the flags existed are recognized by their stream size,
+    /// which used to cover the island table and the padding up to the end of the image.
     #[inline]
+    #[allow(clippy::cast_possible_truncation)]
     pub(crate) fn decode(data: impl AsRef<[u8]>) -> Result<Self> {
         let data = data.as_ref();
         if unlikely(data.len() < QOI_HEADER_SIZE) {
@@ -103,7 +129,22 @@ impl Header {
         if unlikely(magic != QOI_MAGIC) {
             return Err(Error::InvalidMagic { magic });
         }
-        Self::try_new(width, height, n_encode, n_islands, channels, colorspace)
+        let mut header = Self::try_new(width, height, n_encode, n_islands, channels, colorspace)?;
+        let body = &data[QOI_HEADER_SIZE..];
+        let tail = (n_islands as usize).saturating_mul(QOI_ISLAND_SIZE) + QOI_PADDING_SIZE;
+        // a legacy image ends right after its table and padding, both covered by the stream
+        let legacy_stream = header.n_encode().checked_sub(tail).filter(|_| {
+            body.len() == header.n_encode() && body.ends_with(&QOI_PADDING)
+        });
+        match body.get(header.extension_offset()..).and_then(|ext| ext.get(..QOI_EXTENSION_SIZE)) {
+            Some(ext) if ext[..4] == QOI_EXTENSION_MAGIC.to_be_bytes() => header.flags = ext[4],
+            _ => {
+                if let Some(n_stream) = legacy_stream {
+                    header.n_encode = n_stream as u32; // can't truncate
+                }
+            }
+        }
+        Ok(header)
     }
 
     /// Returns a number of pixels in the image.
@@ -118,12 +159,73 @@ impl Header {
         self.n_islands as usize
     }
 
-    /// Returns a number of encoded for the image.
+    /// Returns true if island records carry pixel statistics.
+    #[inline]
+    pub const fn has_island_stats(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_STATS != 0
+    }
+
+    /// Returns true if a foreground mask is stored for each island.
+    #[inline]
+    pub const fn has_island_masks(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_MASKS != 0
+    }
+
+    /// Returns true if each island is stored as an independently decodable sub-stream.
+    #[inline]
+    pub const fn has_island_streams(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_STREAMS != 0
+    }
+
+    /// Returns true if the contours of each island are stored.
+    #[inline]
+    pub const fn has_island_contours(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_CONTOURS != 0
+    }
+
+    /// Returns true if the island records carry persistent tracking ids.
+    #[inline]
+    pub const fn has_island_ids(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_IDS != 0
+    }
+
+    /// Returns true if only the islands are stored, on top of a uniform background.
+    ///
+    /// In this case, the pixel stream consists of the 4-byte RGBA background colour,
+    /// and the islands are stored as sub-streams (see [`Header::has_island_streams`]).
+    #[inline]
+    pub const fn is_sparse(&self) -> bool {
+        self.flags & QOI_FLAG_SPARSE != 0
+    }
+
+    /// Returns true if the image was trimmed by the encoder, in which case the original
+    /// canvas is stored in the extension section (see [`Canvas`]).
+    #[inline]
+    pub const fn is_trimmed(&self) -> bool {
+        self.flags & QOI_FLAG_TRIMMED != 0
+    }
+
+    /// Returns the size of the encoded pixel stream in bytes.
+    ///
+    /// The island table starts right after the pixel stream, so this can be used to
+    /// locate it without decoding any pixels.
     #[inline]
     pub const fn n_encode(&self) -> usize {
         self.n_encode as usize
     }
 
+    /// Returns the offset of the island table from the end of the header.
+    #[inline]
+    pub(crate) const fn island_table_offset(&self) -> usize {
+        self.n_encode()
+    }
+
+    /// Returns the offset of the extension section from the end of the header.
+    #[inline]
+    pub(crate) const fn extension_offset(&self) -> usize {
+        self.island_table_offset().saturating_add(self.n_islands().saturating_mul(QOI_ISLAND_SIZE))
+    }
+
     /// Returns the total number of bytes in the raw pixel array.
     ///
     /// This may come useful when pre-allocating a buffer to decode the image into.
@@ -140,3 +242,64 @@ impl Header {
         encode_max_len(self.width, self.height, self.channels)
     }
 }
+
+/// Original canvas of an image trimmed by the encoder, see
+/// [`Encoder::with_trim`](crate::Encoder::with_trim).
+///
+/// The stored image is a crop of the canvas, with its top-left corner at column `x`
+/// and row `y`; everything outside of it is transparent.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Canvas {
+    /// Canvas width in pixels
+    pub width: u32,
+    /// Canvas height in pixels
+    pub height: u32,
+    /// Column of the top-left corner of the stored image
+    pub x: u32,
+    /// Row of the top-left corner of the stored image
+    pub y: u32,
+}
+
+impl Canvas {
+    /// Returns the rectangle covered by the stored image on the canvas.
+    #[inline]
+    pub const fn rect(&self, header: &Header) -> Rect {
+        Rect::new(self.x, self.y, header.width, header.height)
+    }
+
+    /// Serializes the canvas into a bytes array.
+    #[inline]
+    pub(crate) fn encode(&self) -> [u8; QOI_CANVAS_SIZE] {
+        let mut out = [0; QOI_CANVAS_SIZE];
+        out[..4].copy_from_slice(&self.width.to_be_bytes());
+        out[4..8].copy_from_slice(&self.height.to_be_bytes());
+        out[8..12].copy_from_slice(&self.x.to_be_bytes());
+        out[12..16].copy_from_slice(&self.y.to_be_bytes());
+        out
+    }
+
+    /// Deserializes the canvas from a byte array and checks that it holds the image.
+    #[inline]
+    pub(crate) fn decode(data: impl AsRef<[u8]>, header: &Header) -> Result<Self> {
+        let data = data.as_ref();
+        if unlikely(data.len() < QOI_CANVAS_SIZE) {
+            return Err(Error::UnexpectedBufferEnd);
+        }
+        let v = cast_slice::<_, [u8; 4]>(&data[..QOI_CANVAS_SIZE]);
+        let canvas = Self {
+            width: u32::from_be_bytes(v[0]),
+            height: u32::from_be_bytes(v[1]),
+            x: u32::from_be_bytes(v[2]),
+            y: u32::from_be_bytes(v[3]),
+        };
+        let (channels, colorspace) = (header.channels, header.colorspace);
+        let full = Header::try_new(canvas.width, canvas.height, 0, 0, channels, colorspace)?;
+        let rect = canvas.rect(header);
+        if unlikely(rect.clip_to(&full) != Some(rect)) {
+            let Rect { x, y, width, height } = rect;
+            return Err(Error::InvalidRegion { x, y, width, height });
+        }
+        Ok(canvas)
+    }
+}
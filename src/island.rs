@@ -1,153 +1,987 @@
-use std::collections::{HashSet, VecDeque};
-use std::mem::transmute;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
 use bytemuck::cast_slice;
-use crate::error::{Result};
-use crate::utils::{Writer};
 use rayon::prelude::*;
 
+use core::convert::TryFrom;
+use core::num::NonZeroU32;
+use core::ops::Range;
 
+use crate::consts::{
+    QOI_CANVAS_SIZE, QOI_EXTENSION_MAGIC, QOI_EXTENSION_SIZE, QOI_FLAG_ISLAND_CONTOURS,
+    QOI_FLAG_ISLAND_IDS, QOI_FLAG_ISLAND_MASKS, QOI_FLAG_ISLAND_STATS, QOI_FLAG_ISLAND_STREAMS,
+    QOI_FLAG_TRIMMED, QOI_ISLAND_SIZE,
+};
+use crate::contour::{self, Contour};
+use crate::error::{Error, Result};
+use crate::header::{Canvas, Header};
+use crate::label::{label, Blob, Components};
+use crate::mask::IslandMask;
+use crate::postprocess::{postprocess, Groups};
+use crate::rect::Rect;
+use crate::spatial::IslandIndex;
+use crate::types::{Channels, ColorSpace};
+use crate::utils::Writer;
 
+/// Size of the optional statistics field of an island in bytes.
+const ISLAND_STATS_SIZE: usize = 16;
+/// Size of the optional sub-stream location field of an island in bytes.
+const ISLAND_STREAM_SIZE: usize = 8;
+/// Size of the optional tracking id field of an island in bytes.
+const ISLAND_ID_SIZE: usize = 4;
 
-/// Image Islands: dimensions, channels, color space.
-pub type Point = (u32, u32);
+/// Size of the optional fields of a single island in bytes, given the header flags.
+///
+/// The island table itself only holds the bounding boxes; the optional fields of each
+/// island are stored in the extension section, in the order of their flag bits.
+#[inline]
+const fn island_fields_size(flags: u8) -> usize {
+    let mut size = 0;
+    if flags & QOI_FLAG_ISLAND_STATS != 0 {
+        size += ISLAND_STATS_SIZE;
+    }
+    if flags & QOI_FLAG_ISLAND_STREAMS != 0 {
+        size += ISLAND_STREAM_SIZE;
+    }
+    if flags & QOI_FLAG_ISLAND_IDS != 0 {
+        size += ISLAND_ID_SIZE;
+    }
+    size
+}
+
+/// Offset of an optional field within the fields of an island: the size of all fields before it.
+#[inline]
+const fn island_field_offset(flags: u8, flag: u8) -> usize {
+    island_fields_size(flags & (flag - 1))
+}
+
+/// Offset of the optional fields of the first island from the start of the island table:
+/// they follow the bounding boxes, the extension magic and flags, and the canvas.
+#[inline]
+const fn island_fields_offset(n_islands: usize, flags: u8) -> usize {
+    let canvas = if flags & QOI_FLAG_TRIMMED != 0 { QOI_CANVAS_SIZE } else { 0 };
+    n_islands.saturating_mul(QOI_ISLAND_SIZE) + QOI_EXTENSION_SIZE + canvas
+}
 
+/// Size of the island table along with the extension section up to the island masks.
+#[inline]
+const fn island_table_len(n_islands: usize, flags: u8) -> usize {
+    if flags == 0 {
+        return n_islands.saturating_mul(QOI_ISLAND_SIZE);
+    }
+    let fields_len = n_islands.saturating_mul(island_fields_size(flags));
+    island_fields_offset(n_islands, flags).saturating_add(fields_len)
+}
+
+/// Returns the optional fields of the `index`-th island; the table must be long enough.
+#[inline]
+fn island_fields<'a>(table: &'a [u8], header: &Header, index: usize) -> &'a [u8] {
+    let size = island_fields_size(header.flags);
+    if size == 0 {
+        return &[];
+    }
+    let offset = island_fields_offset(header.n_islands(), header.flags) + index * size;
+    &table[offset..offset + size]
+}
+
+/// Decodes the bounding box of the `index`-th island record, checking that it's
+/// non-empty and lies within the image.
+#[inline]
+fn decode_rect(table: &[u8], header: &Header, index: usize) -> Result<Rect> {
+    let record = &table[index * QOI_ISLAND_SIZE..(index + 1) * QOI_ISLAND_SIZE];
+    let v = cast_slice::<_, [u8; 4]>(record);
+    let (y, x) = (u32::from_be_bytes(v[0]), u32::from_be_bytes(v[1]));
+    let (bottom, right) = (u32::from_be_bytes(v[2]), u32::from_be_bytes(v[3]));
+    // inverted corners come out as an empty rectangle
+    let width = right.saturating_add(1).saturating_sub(x);
+    let height = bottom.saturating_add(1).saturating_sub(y);
+    let rect = Rect::new(x, y, width, height);
+    if rect.is_empty() || rect.clip_to(header) != Some(rect) {
+        return Err(Error::InvalidIsland { index, x, y, width, height });
+    }
+    Ok(rect)
+}
+
+/// Decodes the location of the sub-stream of an island, relative to the table start.
+#[inline]
+fn decode_stream_range(fields: &[u8], flags: u8) -> Range<usize> {
+    let offset = island_field_offset(flags, QOI_FLAG_ISLAND_STREAMS);
+    let v = cast_slice::<_, [u8; 4]>(&fields[offset..offset + ISLAND_STREAM_SIZE]);
+    let (start, len) = (u32::from_be_bytes(v[0]) as usize, u32::from_be_bytes(v[1]) as usize);
+    start..start.saturating_add(len)
+}
+
+/// Decodes the tracking id of an island; zero means the island has no id.
+#[inline]
+fn decode_id(fields: &[u8], flags: u8) -> Option<u32> {
+    let offset = island_field_offset(flags, QOI_FLAG_ISLAND_IDS);
+    let v = &fields[offset..offset + ISLAND_ID_SIZE];
+    NonZeroU32::new(u32::from_be_bytes([v[0], v[1], v[2], v[3]])).map(NonZeroU32::get)
+}
+
+/// Checks that the table holds a full record for each island in the header, along with
+/// the optional fields of each island.
+#[inline]
+const fn validate_table_len(table: &[u8], header: &Header) -> Result<()> {
+    let expected = island_table_len(header.n_islands(), header.flags);
+    if table.len() < expected {
+        return Err(Error::TruncatedIslandTable { expected, found: table.len() });
+    }
+    Ok(())
+}
+
+/// Looks up a single island record in the island table and returns the bounding box
+/// along with the location of its sub-stream (relative to the start of the table),
+/// if sub-streams are stored.
+#[inline]
+pub fn decode_island_record(
+    table: &[u8], header: &Header, index: usize,
+) -> Result<(Rect, Option<Range<usize>>)> {
+    if index >= header.n_islands() {
+        return Err(Error::InvalidIslandIndex { index, n_islands: header.n_islands() });
+    }
+    validate_table_len(table, header)?;
+    let rect = decode_rect(table, header, index)?;
+    if !header.has_island_streams() {
+        return Ok((rect, None));
+    }
+    let stream = decode_stream_range(island_fields(table, header, index), header.flags);
+    if table.len() < stream.end {
+        return Err(Error::UnexpectedBufferEnd);
+    }
+    Ok((rect, Some(stream)))
+}
+
+/// Pixel statistics of an island.
+///
+/// The centroid is compared bitwise, so that islands can be used as keys in maps and sets.
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IslandStats {
+    /// Number of foreground pixels in the island
+    pub pixel_count: u32,
+    /// Mean position of the island pixels as (column, row)
+    pub centroid: (f32, f32),
+    /// Mean colour of the island pixels
+    pub mean_color: [u8; 3],
+    /// Mean alpha of the island pixels (255 for RGB images)
+    pub mean_alpha: u8,
+}
+
+impl PartialEq for IslandStats {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for IslandStats {}
+
+impl Hash for IslandStats {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl IslandStats {
+    /// All fields, with the centroid as raw bits.
+    fn key(&self) -> (u32, (u32, u32), [u8; 3], u8) {
+        let centroid = (self.centroid.0.to_bits(), self.centroid.1.to_bits());
+        (self.pixel_count, centroid, self.mean_color, self.mean_alpha)
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn from_blob(blob: &Blob) -> Self {
+        let count = blob.count.max(1);
+        let mean = |sum: u64| ((sum + count / 2) / count) as u8;
+        let [r, g, b, a] = blob.sum_rgba;
+        Self {
+            pixel_count: blob.count as u32,
+            centroid: (
+                (blob.sum_x as f64 / count as f64) as f32,
+                (blob.sum_y as f64 / count as f64) as f32,
+            ),
+            mean_color: [mean(r), mean(g), mean(b)],
+            mean_alpha: mean(a),
+        }
+    }
+
+    fn encode(&self) -> [u8; ISLAND_STATS_SIZE] {
+        let mut out = [0; ISLAND_STATS_SIZE];
+        out[..4].copy_from_slice(&self.pixel_count.to_be_bytes());
+        out[4..8].copy_from_slice(&self.centroid.0.to_bits().to_be_bytes());
+        out[8..12].copy_from_slice(&self.centroid.1.to_bits().to_be_bytes());
+        out[12..15].copy_from_slice(&self.mean_color);
+        out[15] = self.mean_alpha;
+        out
+    }
+
+    fn decode(data: &[u8]) -> Self {
+        let v = cast_slice::<_, [u8; 4]>(&data[..12]);
+        let centroid_x = f32::from_bits(u32::from_be_bytes(v[1]));
+        let centroid_y = f32::from_bits(u32::from_be_bytes(v[2]));
+        Self {
+            pixel_count: u32::from_be_bytes(v[0]),
+            centroid: (centroid_x, centroid_y),
+            mean_color: [data[12], data[13], data[14]],
+            mean_alpha: data[15],
+        }
+    }
+}
+
+/// A connected group of foreground pixels.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Island {
-    /// Top right of island
-    pub top_left: Option<Point>,
-    /// Btm left of island
-    pub btm_right: Option<Point>,
+    /// Bounding box of the island
+    pub rect: Rect,
+    /// Pixel statistics, if they were computed or stored in the image
+    pub stats: Option<IslandStats>,
+    /// Persistent id assigned by an [`IslandTracker`](crate::IslandTracker), if any (never 0)
+    pub id: Option<u32>,
 }
 
-pub struct Islands {
-    pub islands: HashSet<Island>
+impl Island {
+    /// Creates a new island from its bounding box.
+    #[inline]
+    pub const fn new(rect: Rect) -> Self {
+        Self { rect, stats: None, id: None }
+    }
+
+    /// Key defining the island order: top-left corner, then bottom-right corner.
+    #[inline]
+    pub(crate) const fn raster_key(&self) -> (u32, u32, u32, u32) {
+        (self.rect.y, self.rect.x, self.rect.bottom(), self.rect.right())
+    }
 }
 
-impl Islands {
-    /// Creates a island map
+impl From<Rect> for Island {
     #[inline]
-    pub(crate) fn find_islands(points: &HashSet<Point>) -> Vec<Island> {
+    fn from(rect: Rect) -> Self {
+        Self::new(rect)
+    }
+}
 
-        let mut islands: Vec<Island> = Vec::new();
-        let mut used_points: HashSet<Point> = HashSet::new();
+/// Area the encoder crops the image to, see [`Encoder::with_trim`](crate::Encoder::with_trim).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Trim {
+    /// Union of the island bounding boxes, as found with the island options
+    Islands,
+    /// Bounding box of all foreground pixels, including those of filtered out islands
+    Foreground,
+}
 
-        for point in points {
-            if !used_points.contains(point) {
-                let mut island = Island {
-                    top_left: None,
-                    btm_right: None
-                };
-                bfs(&points, &mut used_points, &mut island, point);
-                // println!("island:{}, {}, {}, {}", island.top_left.unwrap().0, island.top_left.unwrap().1,
-                //          island.btm_right.unwrap().0, island.btm_right.unwrap().1);
-                if island.top_left != None && island.btm_right != None {
-                    islands.push(island);
-                }
-                // println!("points - used_points:{}, {}", points.len(), used_points.len());
+/// Pixel neighbourhood used to decide whether two foreground pixels are connected.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Connectivity {
+    /// Orthogonal neighbours only (left, right, up, down)
+    Four,
+    /// Orthogonal and diagonal neighbours
+    Eight,
+}
+
+impl Connectivity {
+    /// Returns true if only orthogonal neighbours are connected.
+    pub const fn is_four(self) -> bool {
+        matches!(self, Self::Four)
+    }
+
+    /// Returns true if diagonal neighbours are connected as well.
+    pub const fn is_eight(self) -> bool {
+        matches!(self, Self::Eight)
+    }
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Self::Four
+    }
+}
+
+/// User-provided predicate over the raw bytes of a single pixel.
+type PixelPredicate = dyn Fn(&[u8]) -> bool + Send + Sync;
+
+/// Predicate deciding which pixels are foreground, i.e. belong to islands.
+///
+/// Predicates receive the raw bytes of a single pixel (3 or 4 bytes, depending
+/// on the number of channels in the image).
+#[derive(Clone)]
+pub enum Foreground {
+    /// Pixels that are not all-zero bytes
+    NonZero,
+    /// Pixels with alpha strictly above the threshold (RGB pixels are always opaque)
+    Alpha { threshold: u8 },
+    /// Pixels differing from the key colour by more than `tolerance` in any channel
+    ChromaKey { color: [u8; 4], tolerance: u8 },
+    /// Pixels differing from the top-left corner pixel by more than `tolerance` in any channel
+    Background { tolerance: u8 },
+    /// Pixels for which the user-provided closure returns true
+    Custom(Arc<PixelPredicate>),
+}
+
+impl Foreground {
+    /// Creates a custom predicate from a closure over raw pixel bytes.
+    pub fn custom(predicate: impl Fn(&[u8]) -> bool + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(predicate))
+    }
+
+    /// Builds the foreground mask for a row-major array of `N`-channel pixels.
+    pub(crate) fn mask<const N: usize>(&self, data: &[u8]) -> Vec<bool> {
+        #[inline]
+        fn differs(px: &[u8], key: &[u8], tolerance: u8) -> bool {
+            px.iter().zip(key).any(|(&a, &b)| a.max(b) - a.min(b) > tolerance)
+        }
+
+        let pixels = data.par_chunks_exact(N);
+        match self {
+            Self::NonZero => pixels.map(|px| px.iter().any(|&c| c != 0)).collect(),
+            Self::Alpha { threshold } => pixels.map(|px| N < 4 || px[3] > *threshold).collect(),
+            Self::ChromaKey { color, tolerance } => {
+                pixels.map(|px| differs(px, color, *tolerance)).collect()
+            }
+            Self::Background { tolerance } => {
+                let corner = &data[..N.min(data.len())];
+                pixels.map(|px| differs(px, corner, *tolerance)).collect()
+            }
+            Self::Custom(predicate) => pixels.map(|px| predicate(px)).collect(),
+        }
+    }
+
+    /// Returns the same predicate for a crop of the image: the background colour is
+    /// taken from the corner of the full image rather than from the corner of the crop.
+    pub(crate) fn anchored(&self, data: &[u8], channels: usize) -> Self {
+        match self {
+            Self::Background { tolerance } if data.len() >= channels => {
+                let mut color = [0xff; 4];
+                color[..channels].copy_from_slice(&data[..channels]);
+                Self::ChromaKey { color, tolerance: *tolerance }
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Default for Foreground {
+    fn default() -> Self {
+        Self::NonZero
+    }
+}
+
+impl Debug for Foreground {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NonZero => write!(f, "NonZero"),
+            Self::Alpha { threshold } => {
+                f.debug_struct("Alpha").field("threshold", threshold).finish()
+            }
+            Self::ChromaKey { color, tolerance } => f
+                .debug_struct("ChromaKey")
+                .field("color", color)
+                .field("tolerance", tolerance)
+                .finish(),
+            Self::Background { tolerance } => {
+                f.debug_struct("Background").field("tolerance", tolerance).finish()
             }
+            Self::Custom(_) => write!(f, "Custom(..)"),
         }
+    }
+}
+
+/// Options controlling island detection.
+#[derive(Clone, Debug, Default)]
+pub struct IslandOptions {
+    connectivity: Connectivity,
+    foreground: Foreground,
+    // header flags of the island data to produce, see `IslandOptions::flags`
+    flags: u8,
+    contour_tolerance: f32,
+    labels: bool,
+    min_area: u32,
+    padding: u32,
+    merge_distance: Option<u32>,
+    max_islands: Option<usize>,
+}
+
+impl IslandOptions {
+    /// Creates default options: 4-connectivity, non-zero pixels are foreground.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns new options with modified connectivity.
+    #[inline]
+    #[must_use]
+    pub const fn with_connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    /// Returns new options with modified foreground predicate.
+    #[inline]
+    #[must_use]
+    pub fn with_foreground(mut self, foreground: Foreground) -> Self {
+        self.foreground = foreground;
+        self
+    }
+
+    /// Returns new options with per-island pixel statistics enabled or disabled.
+    ///
+    /// When enabled, each island carries [`IslandStats`] which the encoder stores
+    /// in an extended island record.
+    #[inline]
+    #[must_use]
+    pub const fn with_stats(self, stats: bool) -> Self {
+        self.with_flag(QOI_FLAG_ISLAND_STATS, stats)
+    }
+
+    /// Returns new options with per-island foreground masks enabled or disabled.
+    ///
+    /// When enabled, the encoder stores an [`IslandMask`] for each island so that
+    /// the exact island shapes can be recovered after decoding.
+    #[inline]
+    #[must_use]
+    pub const fn with_masks(self, masks: bool) -> Self {
+        self.with_flag(QOI_FLAG_ISLAND_MASKS, masks)
+    }
+
+    /// Returns new options with per-island sub-streams enabled or disabled.
+    ///
+    /// When enabled, the encoder additionally stores the bounding box of each island
+    /// as a self-contained QOI stream, so that islands can be decoded on their own
+    /// (and in parallel) via [`Decoder::decode_island`](crate::Decoder::decode_island).
+    #[inline]
+    #[must_use]
+    pub const fn with_streams(self, streams: bool) -> Self {
+        self.with_flag(QOI_FLAG_ISLAND_STREAMS, streams)
+    }
+
+    /// Returns new options with per-island contours enabled or disabled.
+    ///
+    /// When enabled, the boundaries of each island (see [`Contour`]) are traced and
+    /// stored by the encoder, which allows for precise hit-testing of irregular shapes.
+    #[inline]
+    #[must_use]
+    pub const fn with_contours(self, contours: bool) -> Self {
+        self.with_flag(QOI_FLAG_ISLAND_CONTOURS, contours)
+    }
+
+    /// Returns new options with the per-pixel label map enabled or disabled.
+    ///
+    /// When enabled, [`Islands::labels`] holds the label of every pixel, produced by
+    /// the same pass that finds the islands. The label map is never stored in the image.
+    #[inline]
+    #[must_use]
+    pub const fn with_labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Returns new options with modified contour simplification tolerance in pixels.
+    ///
+    /// Contours are simplified so that no dropped vertex is further than the tolerance
+    /// away from the resulting polygon; zero (the default) keeps every corner.
+    #[inline]
+    #[must_use]
+    pub const fn with_contour_tolerance(mut self, tolerance: f32) -> Self {
+        self.contour_tolerance = tolerance;
+        self
+    }
+
+    /// Returns new options dropping islands with fewer than `min_area` foreground pixels.
+    ///
+    /// This is useful to get rid of stray pixels; by default, all islands are kept.
+    #[inline]
+    #[must_use]
+    pub const fn with_min_area(mut self, min_area: u32) -> Self {
+        self.min_area = min_area;
+        self
+    }
+
+    /// Returns new options inflating each bounding box by `padding` pixels on every side.
+    ///
+    /// The padded boxes are clamped to the image, and padding is applied before merging.
+    #[inline]
+    #[must_use]
+    pub const fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
 
-        islands
+    /// Returns new options merging islands whose bounding boxes overlap or are
+    /// separated by fewer than `distance` pixels (horizontally or vertically).
+    ///
+    /// The merged island covers the union of the boxes; merging is repeated until no
+    /// more boxes qualify. Pass `None` to disable merging (the default).
+    #[inline]
+    #[must_use]
+    pub const fn with_merge_distance(mut self, distance: Option<u32>) -> Self {
+        self.merge_distance = distance;
+        self
+    }
+
+    /// Returns new options capping the number of islands.
+    ///
+    /// If there are more islands, only the ones with the most foreground pixels are
+    /// kept (still in raster order). Pass `None` to keep all islands (the default).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_islands(mut self, max_islands: Option<usize>) -> Self {
+        self.max_islands = max_islands;
+        self
     }
 
-    /// Serializes the header into a bytes array.
+    /// Returns the pixel connectivity.
     #[inline]
-    pub(crate) fn encode<W: Writer>(mut buf: W, islands: &Vec<Island>) -> Result<W> {
+    pub const fn connectivity(&self) -> Connectivity {
+        self.connectivity
+    }
+
+    /// Returns the foreground predicate.
+    #[inline]
+    pub const fn foreground(&self) -> &Foreground {
+        &self.foreground
+    }
 
-        for island in islands {
-            let top_left = island.top_left.unwrap();
-            let btm_right = island.btm_right.unwrap();
+    /// Returns true if per-island pixel statistics are computed.
+    #[inline]
+    pub const fn stats(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_STATS != 0
+    }
+
+    /// Returns true if per-island foreground masks are computed.
+    #[inline]
+    pub const fn masks(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_MASKS != 0
+    }
+
+    /// Returns true if per-island sub-streams are encoded.
+    #[inline]
+    pub const fn streams(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_STREAMS != 0
+    }
 
-            let bytes: [u8; 4] = unsafe { transmute(top_left.0.to_be()) };
-            buf = buf.write_many(&bytes).unwrap();
-            let bytes: [u8; 4] = unsafe { transmute(top_left.1.to_be()) };
-            buf = buf.write_many(&bytes).unwrap();
-            let bytes: [u8; 4] = unsafe { transmute(btm_right.0.to_be()) };
-            buf = buf.write_many(&bytes).unwrap();
-            let bytes: [u8; 4] = unsafe { transmute(btm_right.1.to_be()) };
-            buf = buf.write_many(&bytes).unwrap();
+    /// Returns true if per-island contours are computed.
+    #[inline]
+    pub const fn contours(&self) -> bool {
+        self.flags & QOI_FLAG_ISLAND_CONTOURS != 0
+    }
+
+    /// Returns true if the per-pixel label map is produced.
+    #[inline]
+    pub const fn labels(&self) -> bool {
+        self.labels
+    }
+
+    /// Returns the contour simplification tolerance in pixels.
+    #[inline]
+    pub const fn contour_tolerance(&self) -> f32 {
+        self.contour_tolerance
+    }
+
+    /// Returns the minimum number of foreground pixels in an island.
+    #[inline]
+    pub const fn min_area(&self) -> u32 {
+        self.min_area
+    }
+
+    /// Returns the number of pixels the bounding boxes are inflated by.
+    #[inline]
+    pub const fn padding(&self) -> u32 {
+        self.padding
+    }
+
+    /// Returns the distance below which islands are merged, if merging is enabled.
+    #[inline]
+    pub const fn merge_distance(&self) -> Option<u32> {
+        self.merge_distance
+    }
+
+    /// Returns the maximum number of islands, if capped.
+    #[inline]
+    pub const fn max_islands(&self) -> Option<usize> {
+        self.max_islands
+    }
+
+    /// Header flags describing the island data produced with these options.
+    #[inline]
+    pub(crate) const fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns new options with the given header flag set or cleared.
+    #[inline]
+    const fn with_flag(mut self, flag: u8, enabled: bool) -> Self {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
         }
+        self
+    }
+}
 
-        Ok(buf)
+/// Islands of an image, in raster order of their top-left corners.
+///
+/// Ties are broken by the bottom-right corner, so the order (and hence the
+/// encoded island table) is fully determined by the image contents.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Islands {
+    /// Islands in raster order of their top-left corners, ties broken by the bottom-right
+    /// corner; the island at index `k` is marked with label `k + 1` in the label map
+    pub islands: Vec<Island>,
+    /// Foreground masks, one per island (empty if masks weren't requested or stored)
+    pub masks: Vec<IslandMask>,
+    /// Contours, one list per island (empty if contours weren't requested or stored)
+    pub contours: Vec<Vec<Contour>>,
+    /// Row-major label of every pixel, 0 for background and `k` for pixels of the
+    /// `k`-th island (1-based); empty if the label map wasn't requested
+    pub labels: Vec<u32>,
+}
+
+impl Islands {
+    /// Returns the number of islands.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.islands.len()
+    }
+
+    /// Returns true if there are no islands.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.islands.is_empty()
     }
 
-    /// Deserializes the header from a byte array.
+    /// Returns the islands as an ordered slice.
     #[inline]
-    pub(crate) fn decode(data: impl AsRef<[u8]>, n_islands: u32) -> Result<Self> {
+    pub fn as_slice(&self) -> &[Island] {
+        &self.islands
+    }
+
+    /// Returns an iterator over the islands in order.
+    #[inline]
+    pub fn iter(&self) -> core::slice::Iter<'_, Island> {
+        self.islands.iter()
+    }
 
-        let chunk_size = 16;
-        let data = &data.as_ref();
+    /// Detects islands in a row-major array of raw pixels without encoding it.
+    ///
+    /// The islands are returned in the same order as in [`Islands`].
+    ///
+    /// This runs exactly the same island pass as the encoder does, so the result
+    /// is identical to the island table stored by [`Encoder`](crate::Encoder)
+    /// configured with the same options.
+    #[inline]
+    pub fn detect(
+        pixels: impl AsRef<[u8]>, width: u32, height: u32, channels: Channels,
+        options: &IslandOptions,
+    ) -> Result<Vec<Island>> {
+        Ok(Self::from_pixels(pixels, width, height, channels, options)?.islands)
+    }
 
-        let mut islands: HashSet<Island> = Default::default();
-        let chunks_iter = data.chunks(chunk_size);
+    /// Same as [`Islands::detect`], but also returns the masks, the contours and the
+    /// label map of the islands if they are enabled in the options.
+    #[inline]
+    pub fn from_pixels(
+        pixels: impl AsRef<[u8]>, width: u32, height: u32, channels: Channels,
+        options: &IslandOptions,
+    ) -> Result<Self> {
+        let pixels = pixels.as_ref();
+        let header = Header::try_new(width, height, 0, 0, channels, ColorSpace::default())?;
+        if pixels.len() != header.n_bytes() {
+            return Err(Error::InvalidImageLength { size: pixels.len(), width, height });
+        }
+        Ok(Self::detect_impl(pixels, &header, options))
+    }
 
-        let mut islands_count = 0;
-        for chunk in chunks_iter {
-            islands_count += 1;
-            if islands_count > n_islands {
-                break
+    /// Finds islands of changed pixels (dirty rectangles) between two frames.
+    ///
+    /// Both frames must be row-major arrays of raw pixels of the same dimensions; a pixel
+    /// is foreground if any of its channels differ between them, so the foreground
+    /// predicate in the options is ignored. Everything else works the same way as in
+    /// [`Islands::from_pixels`], with statistics computed over the pixels of `next`.
+    #[inline]
+    pub fn diff(
+        prev: impl AsRef<[u8]>, next: impl AsRef<[u8]>, width: u32, height: u32,
+        channels: Channels, options: &IslandOptions,
+    ) -> Result<Self> {
+        let (prev, next) = (prev.as_ref(), next.as_ref());
+        let header = Header::try_new(width, height, 0, 0, channels, ColorSpace::default())?;
+        for size in [prev.len(), next.len()].iter().copied() {
+            if size != header.n_bytes() {
+                return Err(Error::InvalidImageLength { size, width, height });
             }
-            let v = cast_slice::<_, [u8; 4]>(&chunk);
-            let top_left = (u32::from_be_bytes(v[0]), u32::from_be_bytes(v[1]));
-            let btm_right = (u32::from_be_bytes(v[2]), u32::from_be_bytes(v[3]));
-            let island = Island {
-                top_left: Some(top_left),
-                btm_right: Some(btm_right)
-            };
-            islands.insert(island);
         }
+        let n = channels.as_u8() as usize;
+        let mask = prev.par_chunks_exact(n).zip(next.par_chunks_exact(n)).map(|(a, b)| a != b);
+        Ok(Self::from_mask_impl(&mask.collect::<Vec<_>>(), next, &header, options))
+    }
 
-        // Self::try_new(width, height, channels, colorspace)
-        Ok(Islands { islands })
+    /// Builds a spatial index over the island bounding boxes, see [`IslandIndex`].
+    #[inline]
+    pub fn index(&self) -> IslandIndex {
+        IslandIndex::new(&self.islands)
     }
-}
 
-fn bfs(points: &HashSet<Point>, used_points: &mut HashSet<Point>, island: &mut Island, point: &Point) {
-    let mut q = VecDeque::new();
-    q.push_back(point.clone());
+    /// Returns the foreground mask of the `i`-th island, if masks are available.
+    #[inline]
+    pub fn mask(&self, i: usize) -> Option<&IslandMask> {
+        self.masks.get(i)
+    }
 
-    while let Some(point) = q.pop_front() {
-        if points.contains(&point) && !used_points.contains(&point) {
-            used_points.insert(point.clone());
+    /// Returns the contours of the `i`-th island, if contours are available.
+    #[inline]
+    pub fn contours(&self, i: usize) -> Option<&[Contour]> {
+        self.contours.get(i).map(Vec::as_slice)
+    }
 
-            if let Some(top_left) = island.top_left {
-                if point.0 < top_left.0 {
-                    island.top_left = Some((point.0, top_left.1));
-                }
-                if point.1 < top_left.1 {
-                    island.top_left = Some((top_left.0, point.1));
-                }
-            } else {
-                island.top_left = Some(point.clone());
+    #[inline]
+    pub(crate) fn detect_impl(pixels: &[u8], header: &Header, options: &IslandOptions) -> Self {
+        let mask = match header.channels {
+            Channels::Rgb => options.foreground().mask::<3>(pixels),
+            Channels::Rgba => options.foreground().mask::<4>(pixels),
+        };
+        Self::from_mask_impl(&mask, pixels, header, options)
+    }
+
+    /// Runs the island pass over a foreground mask computed from (or along with) the pixels.
+    pub(crate) fn from_mask_impl(
+        mask: &[bool], pixels: &[u8], header: &Header, options: &IslandOptions,
+    ) -> Self {
+        let pixels =
+            if options.stats() { Some((pixels, header.channels.as_u8() as usize)) } else { None };
+        let (islands, labels) =
+            Self::label_impl(mask, header.width, header.height, options, pixels);
+        let mut masks = if options.masks() || options.contours() {
+            IslandMask::from_labels(&labels, header.width, &islands)
+        } else {
+            Vec::new()
+        };
+        let contours = if options.contours() {
+            let (connectivity, tolerance) = (options.connectivity(), options.contour_tolerance());
+            masks.par_iter().map(|mask| mask.contours(connectivity, tolerance)).collect()
+        } else {
+            Vec::new()
+        };
+        if !options.masks() {
+            masks.clear();
+        }
+        let labels = if options.labels() { labels } else { Vec::new() };
+        Self { islands, masks, contours, labels }
+    }
+
+    /// Finds connected components (one island per blob) in a row-major foreground mask.
+    ///
+    /// The mask must contain exactly `width * height` values. The islands are returned
+    /// in the same order as in [`Islands`].
+    #[inline]
+    pub fn find_islands(
+        mask: &[bool], width: u32, height: u32, connectivity: Connectivity,
+    ) -> Result<Vec<Island>> {
+        let header =
+            Header::try_new(width, height, 0, 0, Channels::default(), ColorSpace::default())?;
+        if mask.len() != header.n_pixels() {
+            return Err(Error::InvalidImageLength { size: mask.len(), width, height });
+        }
+        let options = IslandOptions::new().with_connectivity(connectivity);
+        Ok(Self::label_impl(mask, width, height, &options, None).0)
+    }
+
+    /// Labels the mask, post-processes the components and returns the ordered islands
+    /// along with the per-pixel label map, where label `k` (1-based) marks the pixels
+    /// of the `k`-th island and 0 marks pixels that don't belong to any island.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn label_impl(
+        mask: &[bool], width: u32, height: u32, options: &IslandOptions,
+        pixels: Option<(&[u8], usize)>,
+    ) -> (Vec<Island>, Vec<u32>) {
+        let Components { mut labels, blobs } =
+            label(mask, width, height, options.connectivity(), pixels);
+        let Groups { groups, owners } = postprocess(&blobs, width, height, options);
+        let islands = groups
+            .iter()
+            .map(|(rect, blob)| Island {
+                rect: *rect,
+                stats: pixels.map(|_| IslandStats::from_blob(blob)),
+                id: None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut order = (0..islands.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| islands[i].raster_key());
+        let mut rank = vec![0; islands.len()];
+        for (i, &k) in order.iter().enumerate() {
+            rank[k] = i as u32 + 1;
+        }
+        let remap = core::iter::once(0)
+            .chain(owners.iter().map(|owner| owner.map_or(0, |g| rank[g])))
+            .collect::<Vec<_>>();
+        labels.par_iter_mut().for_each(|label| *label = remap[*label as usize]);
+
+        (order.into_iter().map(|i| islands[i]).collect(), labels)
+    }
+
+    /// Number of bytes taken by the serialized island table and the sections following it,
+    /// excluding the per-island sub-streams.
+    #[inline]
+    fn encoded_table_len(&self, flags: u8) -> usize {
+        let mut size = island_table_len(self.islands.len(), flags);
+        if flags & QOI_FLAG_ISLAND_MASKS != 0 {
+            size += self
+                .islands
+                .iter()
+                .map(|island| IslandMask::packed_len(&island.rect))
+                .sum::<usize>();
+        }
+        size
+    }
+
+    /// Number of bytes taken by the serialized island table and the sections following it.
+    #[inline]
+    pub(crate) fn encoded_len(&self, flags: u8, streams: &[Vec<u8>]) -> usize {
+        let mut size = self.encoded_table_len(flags);
+        if flags & QOI_FLAG_ISLAND_STREAMS != 0 {
+            size += streams.iter().map(Vec::len).sum::<usize>();
+        }
+        if flags & QOI_FLAG_ISLAND_CONTOURS != 0 {
+            size += self.contours.iter().map(|c| contour::encoded_len(c)).sum::<usize>();
+        }
+        size
+    }
+
+    /// Serializes the island table followed by the extension section into a writer.
+    ///
+    /// The extension section is only written if any flags are set: it starts with its
+    /// magic and the flags, followed by the canvas (if trimmed), the optional fields of
+    /// each island and the optional island sections. If sub-streams are enabled, `streams`
+    /// must hold one encoded stream per island.
+    #[inline]
+    pub(crate) fn encode<W: Writer>(
+        &self, mut buf: W, flags: u8, streams: &[Vec<u8>], canvas: Option<&Canvas>,
+    ) -> Result<W> {
+        for island in &self.islands {
+            let rect = island.rect;
+            buf = buf.write_many(&rect.y.to_be_bytes())?;
+            buf = buf.write_many(&rect.x.to_be_bytes())?;
+            buf = buf.write_many(&(rect.bottom() - 1).to_be_bytes())?;
+            buf = buf.write_many(&(rect.right() - 1).to_be_bytes())?;
+        }
+        if flags == 0 {
+            return Ok(buf);
+        }
+        buf = buf.write_many(&QOI_EXTENSION_MAGIC.to_be_bytes())?;
+        buf = buf.write_one(flags)?;
+        if flags & QOI_FLAG_TRIMMED != 0 {
+            buf = buf.write_many(&canvas.copied().unwrap_or_default().encode())?;
+        }
+        let mut stream_offset = self.encoded_table_len(flags);
+        for (i, island) in self.islands.iter().enumerate() {
+            if flags & QOI_FLAG_ISLAND_STATS != 0 {
+                buf = buf.write_many(&island.stats.unwrap_or_default().encode())?;
+            }
+            if flags & QOI_FLAG_ISLAND_STREAMS != 0 {
+                let len = streams[i].len();
+                let (offset, size) = match (u32::try_from(stream_offset), u32::try_from(len)) {
+                    (Ok(offset), Ok(size)) => (offset, size),
+                    _ => return Err(Error::IslandStreamsTooLarge { size: stream_offset + len }),
+                };
+                buf = buf.write_many(&offset.to_be_bytes())?;
+                buf = buf.write_many(&size.to_be_bytes())?;
+                stream_offset += len;
+            }
+            if flags & QOI_FLAG_ISLAND_IDS != 0 {
+                buf = buf.write_many(&island.id.unwrap_or(0).to_be_bytes())?;
+            }
+        }
+        if flags & QOI_FLAG_ISLAND_MASKS != 0 {
+            for mask in &self.masks {
+                buf = buf.write_many(mask.as_bytes())?;
             }
+        }
+        if flags & QOI_FLAG_ISLAND_STREAMS != 0 {
+            for stream in streams {
+                buf = buf.write_many(stream)?;
+            }
+        }
+        if flags & QOI_FLAG_ISLAND_CONTOURS != 0 {
+            for contours in &self.contours {
+                buf = contour::encode(buf, contours)?;
+            }
+        }
+        Ok(buf)
+    }
 
-            if let Some(btm_right) = island.btm_right {
-                if point.0 > btm_right.0 {
-                    island.btm_right = Some((point.0, btm_right.1));
-                }
-                if point.1 > btm_right.1 {
-                    island.btm_right = Some((btm_right.0, point.1));
+    /// Deserializes the island table and the extension section from a byte array.
+    ///
+    /// The table must hold exactly as many records as there are islands in the header,
+    /// and each bounding box must be non-empty and lie within the image.
+    #[inline]
+    pub(crate) fn decode(data: impl AsRef<[u8]>, header: &Header) -> Result<Self> {
+        let data = data.as_ref();
+        validate_table_len(data, header)?;
+
+        let mut islands = Vec::with_capacity(header.n_islands());
+        let mut streams_len = 0;
+        for index in 0..header.n_islands() {
+            let rect = decode_rect(data, header, index)?;
+            let fields = island_fields(data, header, index);
+            if header.has_island_streams() {
+                streams_len += decode_stream_range(fields, header.flags).len();
+            }
+            let stats =
+                if header.has_island_stats() { Some(IslandStats::decode(fields)) } else { None };
+            let id = if header.has_island_ids() { decode_id(fields, header.flags) } else { None };
+            islands.push(Island { rect, stats, id });
+        }
+
+        let mut data = &data[island_table_len(islands.len(), header.flags)..];
+        let mut masks = Vec::new();
+        if header.has_island_masks() {
+            masks.reserve(islands.len());
+            for island in &islands {
+                let len = IslandMask::packed_len(&island.rect);
+                if data.len() < len {
+                    return Err(Error::UnexpectedBufferEnd);
                 }
-            } else {
-                island.btm_right = Some(point.clone());
+                let (bits, tail) = data.split_at(len);
+                masks.push(IslandMask::from_bits(island.rect, bits.to_vec()));
+                data = tail;
             }
+        }
 
-            if point.0 > 0 {
-                q.push_back((point.0 - 1, point.1));
+        let mut contours = Vec::new();
+        if header.has_island_contours() {
+            data = data.get(streams_len..).ok_or(Error::UnexpectedBufferEnd)?;
+            contours.reserve(islands.len());
+            for _ in &islands {
+                contours.push(contour::decode(&mut data)?);
             }
+        }
 
-            // if point.0 < width {
-                q.push_back((point.0 + 1, point.1));
-            // }
+        Ok(Self { islands, masks, contours, labels: Vec::new() })
+    }
+}
 
-            if point.1 > 0 {
-                q.push_back((point.0, point.1 - 1));
-            }
+impl<'a> IntoIterator for &'a Islands {
+    type Item = &'a Island;
+    type IntoIter = core::slice::Iter<'a, Island>;
 
-            // if point.1 < height {
-                q.push_back((point.0, point.1 + 1));
-            // }
-        }
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
 
+impl IntoIterator for Islands {
+    type Item = Island;
+    type IntoIter = alloc::vec::IntoIter<Island>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.islands.into_iter()
+    }
+}
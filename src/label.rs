@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::island::Connectivity;
+
+/// Marker for background pixels in the union-find forest.
+const BACKGROUND: u32 = u32::MAX;
+
+/// Bounding box (inclusive, in pixels) and pixel sums of a single connected component.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Blob {
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+    pub count: u64,
+    pub sum_x: u64,
+    pub sum_y: u64,
+    /// Channel sums (RGBA, alpha is 255 for RGB pixels); zero if no pixels were provided.
+    pub sum_rgba: [u64; 4],
+}
+
+impl Blob {
+    #[inline]
+    const fn new(x: u32, y: u32) -> Self {
+        Self {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+            count: 0,
+            sum_x: 0,
+            sum_y: 0,
+            sum_rgba: [0; 4],
+        }
+    }
+
+    #[inline]
+    fn add(&mut self, x: u32, y: u32, px: Option<&[u8]>) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+        self.count += 1;
+        self.sum_x += u64::from(x);
+        self.sum_y += u64::from(y);
+        if let Some(px) = px {
+            for (sum, &c) in self.sum_rgba.iter_mut().zip(px) {
+                *sum += u64::from(c);
+            }
+            if px.len() < 4 {
+                self.sum_rgba[3] += 0xff;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn merge(&mut self, other: &Self) {
+        self.min_x = self.min_x.min(other.min_x);
+        self.min_y = self.min_y.min(other.min_y);
+        self.max_x = self.max_x.max(other.max_x);
+        self.max_y = self.max_y.max(other.max_y);
+        self.count += other.count;
+        self.sum_x += other.sum_x;
+        self.sum_y += other.sum_y;
+        for (sum, other) in self.sum_rgba.iter_mut().zip(&other.sum_rgba) {
+            *sum += other;
+        }
+    }
+}
+
+/// Result of labelling a foreground mask.
+pub struct Components {
+    /// Per-pixel labels: 0 is background, `k` is the `k`-th component (1-based).
+    pub labels: Vec<u32>,
+    /// Per-component bounding boxes and sums, `blobs[k - 1]` belongs to label `k`.
+    pub blobs: Vec<Blob>,
+}
+
+#[inline]
+fn find(parent: &mut [u32], offset: usize, mut i: usize) -> usize {
+    // path halving; all indices stored in `parent` are global (hence the offset)
+    while parent[i] as usize - offset != i {
+        let p = parent[i] as usize - offset;
+        parent[i] = parent[p];
+        i = p;
+    }
+    i
+}
+
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+fn union(parent: &mut [u32], offset: usize, a: usize, b: usize) {
+    let (ra, rb) = (find(parent, offset, a), find(parent, offset, b));
+    // always link to the smaller index so that parent[i] <= i holds everywhere
+    if ra < rb {
+        parent[rb] = (ra + offset) as u32;
+    } else if rb < ra {
+        parent[ra] = (rb + offset) as u32;
+    }
+}
+
+/// First pass over a horizontal band of rows; `offset` is the index of its first pixel.
+#[allow(clippy::cast_possible_truncation)]
+fn label_band(
+    parent: &mut [u32], mask: &[bool], width: usize, offset: usize, connectivity: Connectivity,
+) {
+    let diagonal = connectivity.is_eight();
+    for (i, &fg) in mask.iter().enumerate() {
+        if !fg {
+            continue;
+        }
+        parent[i] = (i + offset) as u32;
+        let x = i % width;
+        if x > 0 && mask[i - 1] {
+            union(parent, offset, i, i - 1);
+        }
+        if i >= width {
+            union_above(parent, mask, offset, i, i - width, x, width, diagonal);
+        }
+    }
+}
+
+/// Unions pixel `i` with its upper neighbours, `up` being the index right above it.
+#[inline]
+#[allow(clippy::too_many_arguments)]
+fn union_above(
+    parent: &mut [u32], mask: &[bool], offset: usize, i: usize, up: usize, x: usize, width: usize,
+    diagonal: bool,
+) {
+    if mask[up] {
+        union(parent, offset, i, up);
+    }
+    if diagonal {
+        if x > 0 && mask[up - 1] {
+            union(parent, offset, i, up - 1);
+        }
+        if x + 1 < width && mask[up + 1] {
+            union(parent, offset, i, up + 1);
+        }
+    }
+}
+
+/// Labels connected components of a row-major foreground mask; components are
+/// numbered in the raster order of their first pixel.
+///
+/// If the pixels are provided along with their number of channels, per-component
+/// colour sums are accumulated as well.
+///
+/// The image is split into horizontal bands which are labelled in parallel;
+/// labels are then merged across band seams and resolved in raster order, so
+/// the resulting labels don't depend on the number of threads.
+#[allow(clippy::cast_possible_truncation)]
+pub fn label(
+    mask: &[bool], width: u32, height: u32, connectivity: Connectivity,
+    pixels: Option<(&[u8], usize)>,
+) -> Components {
+    let (width, height) = (width as usize, height as usize);
+    debug_assert_eq!(mask.len(), width * height);
+
+    let mut parent = vec![BACKGROUND; mask.len()];
+    let n_bands = rayon::current_num_threads().max(1);
+    let band_len = ((height + n_bands - 1) / n_bands).max(1) * width;
+
+    parent.par_chunks_mut(band_len).zip(mask.par_chunks(band_len)).enumerate().for_each(
+        |(band, (parent, mask))| label_band(parent, mask, width, band * band_len, connectivity),
+    );
+
+    // merge components that touch across band seams
+    for seam in (band_len..mask.len()).step_by(band_len) {
+        for (x, i) in (seam..seam + width).enumerate() {
+            if mask[i] {
+                union_above(&mut parent, mask, 0, i, i - width, x, width, connectivity.is_eight());
+            }
+        }
+    }
+
+    // since parent[i] <= i, a single raster-order pass resolves every pixel to
+    // a sequential label, reusing the forest buffer for the output
+    let mut n_components = 0;
+    for i in 0..parent.len() {
+        let p = parent[i];
+        parent[i] = if p == BACKGROUND {
+            0
+        } else if p as usize == i {
+            n_components += 1;
+            n_components
+        } else {
+            parent[p as usize]
+        };
+    }
+    let labels = parent;
+
+    let blobs = labels
+        .par_chunks(band_len)
+        .enumerate()
+        .map(|(band, labels)| {
+            let mut blobs = HashMap::<u32, Blob>::new();
+            for (i, &label) in labels.iter().enumerate() {
+                if label != 0 {
+                    let i = band * band_len + i;
+                    let (x, y) = ((i % width) as u32, (i / width) as u32);
+                    let px = pixels.map(|(pixels, n)| &pixels[i * n..(i + 1) * n]);
+                    blobs.entry(label).or_insert_with(|| Blob::new(x, y)).add(x, y, px);
+                }
+            }
+            blobs
+        })
+        .collect::<Vec<_>>();
+
+    let mut out: Vec<Option<Blob>> = vec![None; n_components as usize];
+    for band in blobs {
+        for (label, blob) in band {
+            match &mut out[label as usize - 1] {
+                Some(b) => b.merge(&blob),
+                slot => *slot = Some(blob),
+            }
+        }
+    }
+    Components { labels, blobs: out.into_iter().flatten().collect() }
+}
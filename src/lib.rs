@@ -52,6 +52,13 @@
 //! In that case anything related to `std::io`, `std::error::Error` and heap
 //! allocations is disabled. There is an additional `alloc` feature that can
 //! be activated to bring back the support for heap allocations.
+//!
+//! ### `serde`
+//!
+//! The optional `serde` feature implements `Serialize` and `Deserialize` for image
+//! headers and islands, and provides `coco_annotations()` which converts the island
+//! table of an encoded image to COCO-style `annotations` (bounding boxes, areas and
+//! segmentations from island masks or contours).
 
 // #![forbid(unsafe_code)]
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
@@ -71,12 +78,26 @@ extern crate alloc;
 extern crate std as alloc;
 extern crate core;
 
+mod atlas;
+#[cfg(feature = "serde")]
+mod coco;
+mod contour;
 mod decode;
+mod delta;
 mod encode;
 mod error;
 mod header;
 mod island;
+mod label;
+mod mask;
+mod overlay;
 mod pixel;
+mod postprocess;
+mod rect;
+mod spatial;
+mod sprite;
+mod stream;
+mod tracker;
 mod types;
 mod utils;
 
@@ -84,14 +105,27 @@ mod utils;
 pub mod consts;
 
 #[cfg(any(feature = "alloc", feature = "std"))]
-pub use crate::decode::decode_qoi;
-pub use crate::decode::{decode_header, decode_to_buf, Decoder};
+pub use crate::decode::{decode_islands, decode_qoi};
+pub use crate::decode::{decode_header, decode_to_buf, Bytes, Decoder};
+pub use crate::delta::apply_delta;
 
 #[cfg(any(feature = "alloc", feature = "std"))]
 pub use crate::encode::encode_to_vec;
 pub use crate::encode::{encode_max_len, encode_to_buf, Encoder};
 
+pub use crate::atlas::{pack_atlas, pack_islands, Atlas, AtlasOptions};
+#[cfg(feature = "serde")]
+pub use crate::coco::{coco_annotations, CocoAnnotation, CocoAnnotations, CocoSegmentation};
+pub use crate::contour::Contour;
 pub use crate::error::{Error, Result};
-pub use crate::header::Header;
-pub use crate::island::{Islands, Island};
+pub use crate::header::{Canvas, Header};
+pub use crate::island::{
+    Connectivity, Foreground, Island, IslandOptions, IslandStats, Islands, Trim,
+};
+pub use crate::mask::IslandMask;
+pub use crate::overlay::{draw_islands, OverlayOptions};
+pub use crate::rect::Rect;
+pub use crate::spatial::IslandIndex;
+pub use crate::sprite::{extract_sprites, extract_sprites_from_qoi, SpriteOptions, Sprites};
+pub use crate::tracker::{IslandEvent, IslandTracker};
 pub use crate::types::{Channels, ColorSpace};
@@ -0,0 +1,101 @@
+use rayon::prelude::*;
+
+use crate::contour::{trace, Contour};
+use crate::island::{Connectivity, Island};
+use crate::rect::Rect;
+
+/// Foreground mask of a single island, covering its bounding box.
+///
+/// Bits are stored row by row over the bounding box, most significant bit first,
+/// with no padding between rows.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IslandMask {
+    rect: Rect,
+    bits: Vec<u8>,
+}
+
+impl IslandMask {
+    /// Number of bytes taken by a bit-packed mask of the given rectangle.
+    #[inline]
+    pub(crate) const fn packed_len(rect: &Rect) -> usize {
+        (rect.area() + 7) / 8
+    }
+
+    #[inline]
+    pub(crate) fn from_bits(rect: Rect, bits: Vec<u8>) -> Self {
+        debug_assert_eq!(bits.len(), Self::packed_len(&rect));
+        Self { rect, bits }
+    }
+
+    /// Builds the masks of all islands from a per-pixel label map where label
+    /// `k` (1-based) marks the pixels of `islands[k - 1]`.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn from_labels(labels: &[u32], width: u32, islands: &[Island]) -> Vec<Self> {
+        islands
+            .par_iter()
+            .enumerate()
+            .map(|(i, island)| {
+                let rect = island.rect;
+                let label = i as u32 + 1;
+                let mut bits = vec![0; Self::packed_len(&rect)];
+                let mut bit = 0;
+                for y in rect.y..rect.bottom() {
+                    let row = (y as usize) * (width as usize);
+                    for &l in &labels[row + rect.x as usize..row + rect.right() as usize] {
+                        if l == label {
+                            bits[bit / 8] |= 0x80 >> (bit % 8);
+                        }
+                        bit += 1;
+                    }
+                }
+                Self { rect, bits }
+            })
+            .collect()
+    }
+
+    /// Returns the bounding box covered by the mask.
+    #[inline]
+    pub const fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    /// Returns the bit-packed mask data.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Returns true if the pixel at column `x` and row `y` (in image coordinates)
+    /// belongs to the island; pixels outside of the bounding box never do.
+    #[inline]
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        if !self.rect.contains(x, y) {
+            return false;
+        }
+        let bit =
+            (y - self.rect.y) as usize * self.rect.width as usize + (x - self.rect.x) as usize;
+        self.bits[bit / 8] & (0x80 >> (bit % 8)) != 0
+    }
+
+    /// Returns the number of pixels belonging to the island.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Traces the boundaries of the island (outer boundaries and holes) as polygons.
+    ///
+    /// The connectivity decides whether pixels touching diagonally are enclosed by a
+    /// single boundary; if the tolerance is positive, the polygons are simplified so
+    /// that no dropped vertex is further than `tolerance` pixels away from them.
+    #[inline]
+    pub fn contours(&self, connectivity: Connectivity, tolerance: f32) -> Vec<Contour> {
+        trace(self, connectivity, tolerance)
+    }
+
+    /// Expands the mask into one boolean per pixel of the bounding box, row by row.
+    pub fn to_vec(&self) -> Vec<bool> {
+        (0..self.rect.area()).map(|bit| self.bits[bit / 8] & (0x80 >> (bit % 8)) != 0).collect()
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::island::Islands;
+
+/// Default outline colours, cycled through in island order.
+const DEFAULT_PALETTE: [[u8; 4]; 8] = [
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [0, 0, 255, 255],
+    [255, 255, 0, 255],
+    [255, 0, 255, 255],
+    [0, 255, 255, 255],
+    [255, 128, 0, 255],
+    [128, 0, 255, 255],
+];
+
+/// Options controlling how island outlines are drawn by [`draw_islands`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OverlayOptions {
+    thickness: u32,
+    palette: Vec<[u8; 4]>,
+}
+
+impl Default for OverlayOptions {
+    fn default() -> Self {
+        Self { thickness: 1, palette: DEFAULT_PALETTE.to_vec() }
+    }
+}
+
+impl OverlayOptions {
+    /// Creates default options: 1 pixel thick outlines in eight cycling colours.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns new options with modified outline thickness in pixels.
+    ///
+    /// Outlines are drawn on the inside of the bounding boxes, so they never
+    /// extend beyond the islands.
+    #[inline]
+    #[must_use]
+    pub const fn with_thickness(mut self, thickness: u32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Returns new options drawing all outlines in a single RGBA colour.
+    #[inline]
+    #[must_use]
+    pub fn with_color(mut self, color: [u8; 4]) -> Self {
+        self.palette = vec![color];
+        self
+    }
+
+    /// Returns new options with modified outline colours (RGBA).
+    ///
+    /// Island `k` is drawn with colour `k % palette.len()`; an empty palette
+    /// falls back to the default one.
+    #[inline]
+    #[must_use]
+    pub fn with_palette(mut self, palette: Vec<[u8; 4]>) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Returns the outline thickness in pixels.
+    #[inline]
+    pub const fn thickness(&self) -> u32 {
+        self.thickness
+    }
+
+    /// Returns the outline colours.
+    #[inline]
+    pub fn palette(&self) -> &[[u8; 4]] {
+        &self.palette
+    }
+}
+
+/// Draws the bounding box outline of every island into a row-major array of raw
+/// RGB or RGBA pixels, e.g. as returned by [`decode_qoi`](crate::decode_qoi).
+///
+/// The number of channels is inferred from the buffer length, so pixels decoded
+/// with a different number of channels than stored in the header work as well;
+/// alpha of the outline colours is ignored for RGB pixels.
+pub fn draw_islands(
+    pixels: &mut [u8], header: &Header, islands: &Islands, options: &OverlayOptions,
+) -> Result<()> {
+    let (width, height) = (header.width, header.height);
+    let n_pixels = header.n_pixels();
+    let channels = match pixels.len() {
+        len if len == n_pixels * 3 => 3,
+        len if len == n_pixels * 4 => 4,
+        size => return Err(Error::InvalidImageLength { size, width, height }),
+    };
+    let palette = if options.palette.is_empty() { &DEFAULT_PALETTE[..] } else { &options.palette };
+    let thickness = options.thickness;
+    for (i, island) in islands.iter().enumerate() {
+        let rect = match island.rect.clip_to(header) {
+            Some(rect) => rect,
+            None => continue,
+        };
+        let color = &palette[i % palette.len()][..channels];
+        let mut fill = |y: u32, x0: u32, x1: u32| {
+            let row = y as usize * width as usize;
+            let span = &mut pixels[(row + x0 as usize) * channels..(row + x1 as usize) * channels];
+            for px in span.chunks_exact_mut(channels) {
+                px.copy_from_slice(color);
+            }
+        };
+        let (x0, x1) = (rect.x, rect.right());
+        let band_x = thickness.min(rect.width);
+        let band_y = thickness.min(rect.height);
+        for y in rect.y..rect.bottom() {
+            if y - rect.y < band_y || rect.bottom() - y <= band_y {
+                fill(y, x0, x1);
+            } else {
+                fill(y, x0, x0 + band_x);
+                fill(y, x1 - band_x, x1);
+            }
+        }
+    }
+    Ok(())
+}
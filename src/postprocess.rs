@@ -0,0 +1,123 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use crate::island::IslandOptions;
+use crate::label::Blob;
+use crate::rect::Rect;
+
+/// Islands left after post-processing the raw components.
+pub struct Groups {
+    /// Bounding box (possibly padded) and merged pixel sums of each island, unordered
+    pub groups: Vec<(Rect, Blob)>,
+    /// Index of the island owning each component, `None` if the component was dropped
+    pub owners: Vec<Option<usize>>,
+}
+
+/// Number of empty rows or columns between two rectangles along the larger of the
+/// two axes; zero if they overlap or touch.
+#[inline]
+fn gap(a: &Rect, b: &Rect) -> u32 {
+    let gap_x = a.x.max(b.x).saturating_sub(a.right().min(b.right()));
+    let gap_y = a.y.max(b.y).saturating_sub(a.bottom().min(b.bottom()));
+    gap_x.max(gap_y)
+}
+
+#[inline]
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Applies the post-processing steps configured in the options to the raw components,
+/// in this order: minimum area filter, padding, merging of nearby boxes, count cap.
+pub fn postprocess(
+    blobs: &[Blob], width: u32, height: u32, options: &IslandOptions,
+) -> Groups {
+    let padding = options.padding();
+    let mut owners = vec![None; blobs.len()];
+    let mut groups = Vec::with_capacity(blobs.len());
+    for (k, blob) in blobs.iter().enumerate() {
+        if blob.count < u64::from(options.min_area()) {
+            continue;
+        }
+        let (x0, y0) = (blob.min_x.saturating_sub(padding), blob.min_y.saturating_sub(padding));
+        let x1 = blob.max_x.saturating_add(padding).min(width - 1);
+        let y1 = blob.max_y.saturating_add(padding).min(height - 1);
+        owners[k] = Some(groups.len());
+        groups.push((Rect::from_corners(x0, y0, x1, y1), *blob));
+    }
+
+    if let Some(distance) = options.merge_distance() {
+        // merged boxes grow and may get close to boxes that were already checked,
+        // so keep sweeping (left to right, pruning by the gap along x) until stable
+        let mut parent = (0..groups.len()).collect::<Vec<_>>();
+        let mut alive = (0..groups.len()).collect::<Vec<_>>();
+        loop {
+            alive.sort_by_key(|&i| groups[i].0.x);
+            let mut merged = false;
+            for a in 0..alive.len() {
+                let i = alive[a];
+                if parent[i] != i {
+                    continue;
+                }
+                for &j in &alive[a + 1..] {
+                    if groups[j].0.x.saturating_sub(groups[i].0.right()) >= distance.max(1) {
+                        break;
+                    }
+                    if parent[j] != j {
+                        continue;
+                    }
+                    let (ra, rb) = (groups[i].0, groups[j].0);
+                    if ra.intersects(&rb) || gap(&ra, &rb) < distance {
+                        let blob = groups[j].1;
+                        groups[i].0 = ra.union(&rb);
+                        groups[i].1.merge(&blob);
+                        parent[j] = i;
+                        merged = true;
+                    }
+                }
+            }
+            alive.retain(|&i| parent[i] == i);
+            if !merged {
+                break;
+            }
+        }
+        let mut index = vec![None; groups.len()];
+        let mut out = Vec::with_capacity(alive.len());
+        alive.sort_unstable();
+        for &i in &alive {
+            index[i] = Some(out.len());
+            out.push(groups[i]);
+        }
+        for owner in owners.iter_mut().flatten() {
+            *owner = index[find(&mut parent, *owner)].unwrap_or_default();
+        }
+        groups = out;
+    }
+
+    if let Some(max_islands) = options.max_islands() {
+        if groups.len() > max_islands {
+            // keep the largest islands; ties are broken by position for reproducibility
+            let mut order = (0..groups.len()).collect::<Vec<_>>();
+            order.sort_by_key(|&i| {
+                let (rect, blob) = &groups[i];
+                (core::cmp::Reverse(blob.count), rect.y, rect.x, rect.bottom(), rect.right())
+            });
+            let mut index = vec![None; groups.len()];
+            let mut out = Vec::with_capacity(max_islands);
+            for &i in &order[..max_islands] {
+                index[i] = Some(out.len());
+                out.push(groups[i]);
+            }
+            for owner in &mut owners {
+                *owner = owner.and_then(|g| index[g]);
+            }
+            groups = out;
+        }
+    }
+
+    Groups { groups, owners }
+}
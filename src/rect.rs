@@ -0,0 +1,103 @@
+use crate::header::Header;
+
+/// Axis-aligned rectangle in pixel coordinates.
+///
+/// `x` is the column and `y` is the row of the top-left pixel. The rectangle
+/// covers columns `x..x + width` and rows `y..y + height`, i.e. the left and top
+/// edges are inclusive while the right and bottom edges are exclusive.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    /// Column of the top-left pixel
+    pub x: u32,
+    /// Row of the top-left pixel
+    pub y: u32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+impl Rect {
+    /// Creates a new rectangle from its top-left corner and dimensions.
+    #[inline]
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Creates a rectangle spanning two corners, both inclusive.
+    #[inline]
+    pub(crate) const fn from_corners(x0: u32, y0: u32, x1: u32, y1: u32) -> Self {
+        Self::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1)
+    }
+
+    /// Returns the exclusive right edge (`x + width`).
+    #[inline]
+    pub const fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// Returns the exclusive bottom edge (`y + height`).
+    #[inline]
+    pub const fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    /// Returns the number of pixels covered by the rectangle.
+    #[inline]
+    pub const fn area(&self) -> usize {
+        (self.width as usize).saturating_mul(self.height as usize)
+    }
+
+    /// Returns true if the rectangle doesn't cover any pixels.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Returns true if the pixel at column `x` and row `y` lies inside the rectangle.
+    #[inline]
+    pub const fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Returns true if the two rectangles share at least one pixel.
+    #[inline]
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// Returns the overlapping part of the two rectangles, if any.
+    #[inline]
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let (x0, y0) = (self.x.max(other.x), self.y.max(other.y));
+        let (x1, y1) = (self.right().min(other.right()), self.bottom().min(other.bottom()));
+        if x0 < x1 && y0 < y1 {
+            Some(Self::new(x0, y0, x1 - x0, y1 - y0))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the smallest rectangle containing both rectangles.
+    ///
+    /// Empty rectangles are ignored.
+    #[inline]
+    #[must_use]
+    pub fn union(&self, other: &Self) -> Self {
+        if other.is_empty() {
+            return *self;
+        } else if self.is_empty() {
+            return *other;
+        }
+        let (x0, y0) = (self.x.min(other.x), self.y.min(other.y));
+        let (x1, y1) = (self.right().max(other.right()), self.bottom().max(other.bottom()));
+        Self::new(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    /// Clips the rectangle to the image bounds, returning `None` if nothing is left.
+    #[inline]
+    pub fn clip_to(&self, header: &Header) -> Option<Self> {
+        self.intersection(&Self::new(0, 0, header.width, header.height))
+    }
+}
@@ -0,0 +1,207 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use crate::island::Island;
+use crate::rect::Rect;
+
+/// Uniform grid over the islands for fast point, rectangle and nearest-neighbour queries.
+///
+/// The grid covers the union of the island bounding boxes; each island is registered
+/// in every cell its bounding box overlaps. All queries return island indices (in the
+/// order the islands were passed in) and only consider the bounding boxes; to hit-test
+/// the exact island shapes, check the candidates against their masks or contours.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IslandIndex {
+    rects: Vec<Rect>,
+    bounds: Rect,
+    cell_size: u32,
+    cols: u32,
+    rows: u32,
+    // island indices of each cell, stored back to back: cell `k` holds
+    // `items[offsets[k]..offsets[k + 1]]`
+    offsets: Vec<usize>,
+    items: Vec<u32>,
+}
+
+/// Squared distance from the pixel at column `x` and row `y` to the closest pixel of the
+/// rectangle, zero if it lies inside.
+#[inline]
+fn distance2(rect: &Rect, x: u32, y: u32) -> u64 {
+    let axis = |v: u32, start: u32, end: u32| {
+        u64::from(if v < start { start - v } else { v.saturating_sub(end - 1) })
+    };
+    let (dx, dy) = (axis(x, rect.x, rect.right()), axis(y, rect.y, rect.bottom()));
+    dx * dx + dy * dy
+}
+
+impl IslandIndex {
+    /// Builds an index with a cell size chosen from the number and size of the islands.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn new(islands: &[Island]) -> Self {
+        let bounds = islands.iter().fold(Rect::default(), |acc, island| acc.union(&island.rect));
+        let n = islands.len().max(1) as f64;
+        // roughly one island per cell, but no smaller than a typical island
+        let spread = (bounds.area() as f64 / n).sqrt();
+        let typical = islands
+            .iter()
+            .map(|island| f64::from(island.rect.width.max(island.rect.height)))
+            .sum::<f64>()
+            / n;
+        Self::with_cell_size(islands, spread.max(typical).ceil() as u32)
+    }
+
+    /// Builds an index with square cells of the given size in pixels (at least 1).
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn with_cell_size(islands: &[Island], cell_size: u32) -> Self {
+        let rects = islands.iter().map(|island| island.rect).collect::<Vec<_>>();
+        let bounds = rects.iter().fold(Rect::default(), |acc, rect| acc.union(rect));
+        let cell_size = cell_size.max(1);
+        let cols = (bounds.width + cell_size - 1) / cell_size;
+        let rows = (bounds.height + cell_size - 1) / cell_size;
+        let mut index = Self { rects, bounds, cell_size, cols, rows, ..Self::default() };
+
+        let n_cells = cols as usize * rows as usize;
+        let mut counts = vec![0; n_cells + 1];
+        for rect in index.rects.iter().filter(|rect| !rect.is_empty()) {
+            index.for_each_cell(rect, |cell| counts[cell + 1] += 1);
+        }
+        for k in 0..n_cells {
+            counts[k + 1] += counts[k];
+        }
+        let mut fill = counts.clone();
+        let mut items = vec![0; counts[n_cells]];
+        for (i, rect) in index.rects.iter().enumerate().filter(|(_, rect)| !rect.is_empty()) {
+            index.for_each_cell(rect, |cell| {
+                items[fill[cell]] = i as u32;
+                fill[cell] += 1;
+            });
+        }
+        index.offsets = counts;
+        index.items = items;
+        index
+    }
+
+    /// Returns the number of indexed islands.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    /// Returns true if there are no indexed islands.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Returns the size of the grid cells in pixels.
+    #[inline]
+    pub const fn cell_size(&self) -> u32 {
+        self.cell_size
+    }
+
+    /// Calls `f` with the index of every cell overlapped by a non-empty rectangle.
+    #[inline]
+    fn for_each_cell(&self, rect: &Rect, mut f: impl FnMut(usize)) {
+        let rect = match rect.intersection(&self.bounds) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let cell = |v: u32, origin: u32| (v - origin) / self.cell_size;
+        let (c0, c1) = (cell(rect.x, self.bounds.x), cell(rect.right() - 1, self.bounds.x));
+        let (r0, r1) = (cell(rect.y, self.bounds.y), cell(rect.bottom() - 1, self.bounds.y));
+        for row in r0..=r1 {
+            for col in c0..=c1 {
+                f(row as usize * self.cols as usize + col as usize);
+            }
+        }
+    }
+
+    #[inline]
+    fn cell_items(&self, k: usize) -> &[u32] {
+        &self.items[self.offsets[k]..self.offsets[k + 1]]
+    }
+
+    /// Returns the islands whose bounding boxes contain the pixel at column `x` and row `y`.
+    pub fn at(&self, x: u32, y: u32) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.for_each_cell(&Rect::new(x, y, 1, 1), |k| {
+            let hits = self.cell_items(k).iter().map(|&i| i as usize);
+            out.extend(hits.filter(|&i| self.rects[i].contains(x, y)));
+        });
+        out
+    }
+
+    /// Returns the islands whose bounding boxes intersect the rectangle, in order.
+    pub fn intersecting(&self, rect: &Rect) -> Vec<usize> {
+        let mut out = Vec::new();
+        if rect.is_empty() {
+            return out;
+        }
+        let cell = |v: u32, origin: u32| (v - origin) / self.cell_size;
+        self.for_each_cell(rect, |k| {
+            for i in self.cell_items(k).iter().map(|&i| i as usize) {
+                // an island spanning several cells is only reported by the cell holding
+                // the top-left corner of its overlap with the query
+                if let Some(overlap) = self.rects[i].intersection(rect) {
+                    let col = cell(overlap.x, self.bounds.x) as usize;
+                    let row = cell(overlap.y, self.bounds.y) as usize;
+                    if row * self.cols as usize + col == k {
+                        out.push(i);
+                    }
+                }
+            }
+        });
+        out.sort_unstable();
+        out
+    }
+
+    /// Returns the island whose bounding box is the closest to the pixel at column `x` and
+    /// row `y` (zero distance if the pixel lies inside), the first one in case of ties.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    pub fn nearest(&self, x: u32, y: u32) -> Option<usize> {
+        if self.items.is_empty() {
+            return None;
+        }
+        // cell coordinates of the pixel, possibly outside of the grid
+        let cell = |v: u32, origin: u32| {
+            (i64::from(v) - i64::from(origin)).div_euclid(i64::from(self.cell_size))
+        };
+        let (px, py) = (cell(x, self.bounds.x), cell(y, self.bounds.y));
+        let (cols, rows) = (i64::from(self.cols), i64::from(self.rows));
+        let outside = |p: i64, n: i64| 0.max(-p).max(p - (n - 1));
+        let first_ring = outside(px, cols).max(outside(py, rows));
+        let last_ring = px.max(cols - 1 - px).max(py).max(rows - 1 - py);
+
+        let mut best: Option<(u64, usize)> = None;
+        let visit = |col: i64, row: i64, best: &mut Option<(u64, usize)>| {
+            if col < 0 || col >= cols {
+                return;
+            }
+            for &i in self.cell_items((row * cols + col) as usize) {
+                let candidate = (distance2(&self.rects[i as usize], x, y), i as usize);
+                if best.map_or(true, |best| candidate < best) {
+                    *best = Some(candidate);
+                }
+            }
+        };
+        for ring in first_ring..=last_ring {
+            for row in (py - ring).max(0)..=(py + ring).min(rows - 1) {
+                if (row - py).abs() == ring {
+                    for col in (px - ring).max(0)..=(px + ring).min(cols - 1) {
+                        visit(col, row, &mut best);
+                    }
+                } else {
+                    visit(px - ring, row, &mut best);
+                    visit(px + ring, row, &mut best);
+                }
+            }
+            // islands not seen yet only cover cells further away than this ring, so they
+            // are more than `ring` cells away from the pixel
+            let bound = ring as u64 * u64::from(self.cell_size);
+            if best.map_or(false, |(d2, _)| d2 <= bound * bound) {
+                break;
+            }
+        }
+        best.map(|(_, i)| i)
+    }
+}
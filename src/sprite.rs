@@ -0,0 +1,204 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use rayon::prelude::*;
+
+use crate::decode::Decoder;
+use crate::encode::Encoder;
+use crate::error::Result;
+use crate::header::Header;
+use crate::island::{IslandOptions, Islands};
+use crate::rect::Rect;
+use crate::stream::{crop, paste};
+use crate::types::{Channels, ColorSpace};
+
+/// Options controlling sprite extraction.
+#[derive(Clone, Debug, Default)]
+pub struct SpriteOptions {
+    islands: IslandOptions,
+    padding: u32,
+    trim: bool,
+}
+
+impl SpriteOptions {
+    /// Creates default options: one sprite per island found with default island options,
+    /// no padding and no trimming.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns new options with modified island detection options.
+    #[inline]
+    #[must_use]
+    pub fn with_island_options(mut self, options: IslandOptions) -> Self {
+        self.islands = options;
+        self
+    }
+
+    /// Returns new options adding `padding` transparent (all-zero) pixels around each sprite.
+    #[inline]
+    #[must_use]
+    pub const fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Returns new options with trimming of fully transparent borders enabled or disabled.
+    ///
+    /// When enabled, fully transparent rows and columns are cut off the edges of each
+    /// sprite, and fully transparent sprites are dropped; RGB images are never trimmed.
+    #[inline]
+    #[must_use]
+    pub const fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Returns the island detection options.
+    #[inline]
+    pub const fn island_options(&self) -> &IslandOptions {
+        &self.islands
+    }
+
+    /// Returns the padding around each sprite in pixels.
+    #[inline]
+    pub const fn padding(&self) -> u32 {
+        self.padding
+    }
+
+    /// Returns true if fully transparent borders are trimmed.
+    #[inline]
+    pub const fn trim(&self) -> bool {
+        self.trim
+    }
+}
+
+/// Sprites extracted from a single image.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sprites {
+    /// Encoded QOI image of each sprite
+    pub images: Vec<Vec<u8>>,
+    /// Source rectangle of each sprite in the original image (excluding padding)
+    pub manifest: Vec<Rect>,
+}
+
+impl Sprites {
+    /// Returns the number of sprites.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Returns true if there are no sprites.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+/// Shrinks the rectangle to the pixels with non-zero alpha, `None` if there are none.
+fn trim_rect(pixels: &[u8], width: u32, rect: Rect) -> Option<Rect> {
+    let opaque = |x: u32, y: u32| pixels[(y as usize * width as usize + x as usize) * 4 + 3] != 0;
+    let row_opaque = |y: u32| (rect.x..rect.right()).any(|x| opaque(x, y));
+    let y0 = (rect.y..rect.bottom()).find(|&y| row_opaque(y))?;
+    let y1 = (y0..rect.bottom()).rev().find(|&y| row_opaque(y))?;
+    let col_opaque = |x: u32| (y0..=y1).any(|y| opaque(x, y));
+    let x0 = (rect.x..rect.right()).find(|&x| col_opaque(x))?;
+    let x1 = (x0..rect.right()).rev().find(|&x| col_opaque(x))?;
+    Some(Rect::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+/// Returns the source rectangle of each sprite, trimmed if requested.
+pub fn sprite_rects(
+    pixels: &[u8], header: &Header, islands: &Islands, options: &SpriteOptions,
+) -> Vec<Rect> {
+    let trim = options.trim && header.channels.is_rgba();
+    islands
+        .iter()
+        .filter_map(|island| {
+            if trim {
+                trim_rect(pixels, header.width, island.rect)
+            } else {
+                Some(island.rect)
+            }
+        })
+        .collect()
+}
+
+/// Crops a sprite out of the image, surrounded by transparent padding; returns its
+/// pixels along with its padded dimensions.
+pub fn crop_sprite(
+    pixels: &[u8], header: &Header, rect: Rect, padding: u32,
+) -> (Vec<u8>, u32, u32) {
+    let channels = header.channels.as_u8() as usize;
+    let sprite = crop(pixels, header.width, channels, rect);
+    if padding == 0 {
+        return (sprite, rect.width, rect.height);
+    }
+    let width = rect.width.saturating_add(padding.saturating_mul(2));
+    let height = rect.height.saturating_add(padding.saturating_mul(2));
+    let mut padded = vec![0; width as usize * height as usize * channels];
+    let inner = Rect::new(padding, padding, rect.width, rect.height);
+    paste(&mut padded, width, channels, inner, &sprite);
+    (padded, width, height)
+}
+
+/// Decodes an encoded image along with its islands, detecting them if it has none.
+pub fn decode_with_islands(
+    data: &[u8], options: &SpriteOptions,
+) -> Result<(Header, Vec<u8>, Islands)> {
+    let mut decoder = Decoder::new(data)?;
+    let pixels = decoder.decode_to_vec()?;
+    let header = *decoder.header();
+    let islands = if header.n_islands() == 0 {
+        Islands::detect_impl(&pixels, &header, &options.islands)
+    } else {
+        decoder.islands()?
+    };
+    Ok((header, pixels, islands))
+}
+
+/// Crops the island bounding boxes out of the image and encodes each one separately.
+fn extract_impl(
+    pixels: &[u8], header: &Header, islands: &Islands, options: &SpriteOptions,
+) -> Result<Sprites> {
+    let manifest = sprite_rects(pixels, header, islands, options);
+    let images = manifest
+        .par_iter()
+        .map(|&rect| {
+            let (sprite, width, height) = crop_sprite(pixels, header, rect, options.padding);
+            Encoder::new(&sprite, width, height)?
+                .with_colorspace(header.colorspace)
+                .encode_to_vec(false)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Sprites { images, manifest })
+}
+
+/// Splits a row-major array of raw pixels into sprites, one per island.
+///
+/// Each sprite covers the bounding box of an island (see [`SpriteOptions`] for padding
+/// and trimming) and is encoded as a separate QOI image; the sprites are in the same
+/// order as the islands.
+pub fn extract_sprites(
+    pixels: impl AsRef<[u8]>, width: u32, height: u32, channels: Channels,
+    options: &SpriteOptions,
+) -> Result<Sprites> {
+    let pixels = pixels.as_ref();
+    let islands = Islands::from_pixels(pixels, width, height, channels, &options.islands)?;
+    let header = Header::try_new(width, height, 0, 0, channels, ColorSpace::default())?;
+    extract_impl(pixels, &header, &islands, options)
+}
+
+/// Splits an encoded image into sprites, one per island.
+///
+/// If the image has an island table, its islands are used as they are; otherwise,
+/// the islands are detected with the island options. The sprites keep the number
+/// of channels and the color space of the image.
+pub fn extract_sprites_from_qoi(
+    data: impl AsRef<[u8]>, options: &SpriteOptions,
+) -> Result<Sprites> {
+    let (header, pixels, islands) = decode_with_islands(data.as_ref(), options)?;
+    extract_impl(&pixels, &header, &islands, options)
+}
@@ -0,0 +1,58 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use rayon::prelude::*;
+
+use crate::encode::encode_stream_all;
+use crate::error::Result;
+use crate::header::Header;
+use crate::island::Island;
+use crate::rect::Rect;
+use crate::utils::{BytesMut, Writer};
+
+/// Copies the pixels inside the rectangle into a tightly packed buffer, row by row.
+///
+/// The rectangle must lie within the image.
+pub fn crop(data: &[u8], width: u32, channels: usize, rect: Rect) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rect.area() * channels);
+    for y in rect.y..rect.bottom() {
+        let row = y as usize * width as usize;
+        let (start, end) = (row + rect.x as usize, row + rect.right() as usize);
+        out.extend_from_slice(&data[start * channels..end * channels]);
+    }
+    out
+}
+
+/// Copies a tightly packed block of pixels into the rectangle of an image, row by row.
+///
+/// The rectangle must lie within the image.
+pub fn paste(out: &mut [u8], width: u32, channels: usize, rect: Rect, pixels: &[u8]) {
+    let len = rect.width as usize * channels;
+    for (y, src) in (rect.y..rect.bottom()).zip(pixels.chunks_exact(len)) {
+        let start = (y as usize * width as usize + rect.x as usize) * channels;
+        out[start..start + len].copy_from_slice(src);
+    }
+}
+
+/// Encodes the bounding box of each island as a self-contained QOI stream.
+///
+/// Each sub-stream starts from a fresh index and previous pixel, so it can be
+/// decoded without touching the rest of the image; the islands are encoded in parallel.
+pub fn encode_island_streams(
+    data: &[u8], header: &Header, islands: &[Island],
+) -> Result<Vec<Vec<u8>>> {
+    let channels = header.channels.as_u8() as usize;
+    islands
+        .par_iter()
+        .map(|island| {
+            let pixels = crop(data, header.width, channels, island.rect);
+            // worst case is a full RGB(A) op per pixel, i.e. one extra byte per pixel
+            let mut out = vec![0; pixels.len() + island.rect.area()];
+            let cap = out.len();
+            let n_written = cap
+                - encode_stream_all(BytesMut::new(&mut out), &pixels, header.channels)?.capacity();
+            out.truncate(n_written);
+            Ok(out)
+        })
+        .collect()
+}
@@ -0,0 +1,170 @@
+#[cfg(any(feature = "std", feature = "alloc"))]
+use alloc::{vec, vec::Vec};
+
+use core::cmp::Ordering::Equal;
+
+use crate::island::Island;
+use crate::rect::Rect;
+
+/// Change to an island between two consecutive frames, reported by [`IslandTracker`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IslandEvent {
+    /// Island with no counterpart in the previous frame
+    Appeared { id: u32, rect: Rect },
+    /// Island matched to the previous frame whose bounding box has changed
+    Moved { id: u32, from: Rect, to: Rect },
+    /// Island that broke up: the best matching part kept the id, the other parts got new ones
+    Split { id: u32, into: Vec<u32> },
+    /// Islands that joined: the result kept the id of the best matching one
+    Merged { id: u32, from: Vec<u32> },
+    /// Island from the previous frame with no counterpart in the new frame
+    Disappeared { id: u32, rect: Rect },
+}
+
+/// Assigns persistent ids to islands across a sequence of frames.
+///
+/// Islands of consecutive frames are matched by the overlap of their bounding boxes:
+/// pairs with the highest intersection over union (IoU) are matched first, and each
+/// island of the new frame inherits the id of its match. Islands that only overlap
+/// already matched ones are reported as splits or merges; islands that jump further
+/// than their own size between frames can't be matched.
+///
+/// Ids start at 1 and are never reused.
+#[derive(Clone, Debug)]
+pub struct IslandTracker {
+    min_iou: f32,
+    islands: Vec<Island>,
+    next_id: u32,
+}
+
+impl Default for IslandTracker {
+    fn default() -> Self {
+        Self { min_iou: 0., islands: Vec::new(), next_id: 1 }
+    }
+}
+
+/// Intersection over union of two bounding boxes.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn iou(a: &Rect, b: &Rect) -> f32 {
+    a.intersection(b).map_or(0., |overlap| {
+        let overlap = overlap.area() as f64;
+        (overlap / (a.area() as f64 + b.area() as f64 - overlap)) as f32
+    })
+}
+
+impl IslandTracker {
+    /// Creates a tracker matching islands with any overlap.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new tracker only matching islands with IoU of at least `min_iou`.
+    ///
+    /// Islands must overlap to be matched regardless of the threshold; the default is 0.
+    #[inline]
+    #[must_use]
+    pub const fn with_min_iou(mut self, min_iou: f32) -> Self {
+        self.min_iou = min_iou;
+        self
+    }
+
+    /// Returns the minimum IoU of matched islands.
+    #[inline]
+    pub const fn min_iou(&self) -> f32 {
+        self.min_iou
+    }
+
+    /// Returns the islands of the last frame, along with their ids.
+    #[inline]
+    pub fn islands(&self) -> &[Island] {
+        &self.islands
+    }
+
+    /// Forgets the last frame, so that all islands of the next one appear anew.
+    ///
+    /// Ids keep increasing and are not reused.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.islands.clear();
+    }
+
+    /// Matches the islands of a new frame to the previous one, assigns their ids and
+    /// returns what happened since the previous frame.
+    ///
+    /// The islands are expected to be in raster order, as returned by island detection.
+    /// Events are reported in the order of the new islands, followed by splits and merges,
+    /// followed by disappearances.
+    pub fn update(&mut self, islands: &mut [Island]) -> Vec<IslandEvent> {
+        let prev = &self.islands;
+
+        // candidate pairs, best matches first; both lists are sorted by the top edge
+        let mut pairs = Vec::new();
+        for (i, p) in prev.iter().enumerate() {
+            for (j, c) in islands.iter().enumerate() {
+                if c.rect.y >= p.rect.bottom() {
+                    break;
+                }
+                let iou = iou(&p.rect, &c.rect);
+                if iou > 0. && iou >= self.min_iou {
+                    pairs.push((iou, i, j));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0).unwrap_or(Equal).then((a.1, a.2).cmp(&(b.1, b.2)))
+        });
+
+        let (mut prev_match, mut next_match) = (vec![None; prev.len()], vec![None; islands.len()]);
+        let (mut prev_overlap, mut next_overlap) =
+            (vec![None; prev.len()], vec![None; islands.len()]);
+        for &(_, i, j) in &pairs {
+            if prev_match[i].is_none() && next_match[j].is_none() {
+                prev_match[i] = Some(j);
+                next_match[j] = Some(i);
+            }
+            prev_overlap[i] = prev_overlap[i].or(Some(j));
+            next_overlap[j] = next_overlap[j].or(Some(i));
+        }
+
+        let mut events = Vec::new();
+        let mut splits = vec![Vec::new(); prev.len()];
+        for (j, island) in islands.iter_mut().enumerate() {
+            let id = if let Some(i) = next_match[j] {
+                prev[i].id.unwrap_or_default()
+            } else {
+                self.next_id += 1;
+                self.next_id - 1
+            };
+            island.id = Some(id);
+            match (next_match[j], next_overlap[j]) {
+                (Some(i), _) if prev[i].rect != island.rect => {
+                    events.push(IslandEvent::Moved { id, from: prev[i].rect, to: island.rect });
+                }
+                (None, Some(i)) => splits[i].push(id),
+                (None, None) => events.push(IslandEvent::Appeared { id, rect: island.rect }),
+                _ => {}
+            }
+        }
+        // an unmatched island always overlaps a matched one (or it would have been matched)
+        for (i, into) in splits.into_iter().enumerate().filter(|(_, into)| !into.is_empty()) {
+            events.push(IslandEvent::Split { id: prev[i].id.unwrap_or_default(), into });
+        }
+        let mut merges = vec![Vec::new(); islands.len()];
+        let mut disappeared = Vec::new();
+        for (i, island) in prev.iter().enumerate().filter(|&(i, _)| prev_match[i].is_none()) {
+            let id = island.id.unwrap_or_default();
+            match prev_overlap[i] {
+                Some(j) => merges[j].push(id),
+                None => disappeared.push(IslandEvent::Disappeared { id, rect: island.rect }),
+            }
+        }
+        for (j, from) in merges.into_iter().enumerate().filter(|(_, from)| !from.is_empty()) {
+            events.push(IslandEvent::Merged { id: islands[j].id.unwrap_or_default(), from });
+        }
+        events.extend(disappeared);
+
+        self.islands = islands.to_vec();
+        events
+    }
+}
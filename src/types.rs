@@ -8,6 +8,7 @@ use crate::utils::unlikely;
 /// Note: the color space is purely informative. Although it is saved to the
 /// file header, it does not affect encoding/decoding in any way.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ColorSpace {
     /// sRGB with linear alpha
@@ -61,6 +62,7 @@ impl TryFrom<u8> for ColorSpace {
 
 /// Number of 8-bit channels in a pixel.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Channels {
     /// Three 8-bit channels (RGB)
@@ -0,0 +1,1612 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use qoi::{
+    apply_delta, decode_islands, decode_qoi, draw_islands, encode_max_len, encode_to_buf,
+    encode_to_vec, extract_sprites,
+    extract_sprites_from_qoi, pack_atlas, pack_islands, AtlasOptions, Canvas, Channels,
+    Connectivity, Contour, Decoder, Encoder, Error, Foreground, Island, IslandEvent, IslandIndex,
+    IslandOptions, IslandStats, IslandTracker, Islands, OverlayOptions, Rect, SpriteOptions, Trim,
+};
+
+fn rgba_from_mask(mask: &[bool]) -> Vec<u8> {
+    mask.iter().flat_map(|&fg| if fg { [200, 100, 50, 255] } else { [0; 4] }).collect()
+}
+
+fn mask_from_str(rows: &[&str]) -> (Vec<bool>, u32, u32) {
+    let mask = rows.iter().flat_map(|row| row.chars().map(|c| c == '#')).collect();
+    (mask, rows[0].len() as u32, rows.len() as u32)
+}
+
+fn encoded_islands(mask: &[bool], width: u32, height: u32) -> Vec<Island> {
+    let encoded = encode_to_vec(rgba_from_mask(mask), width, height, true).unwrap();
+    let (header, _, islands) = decode_qoi(&encoded).unwrap();
+    assert_eq!(header.n_islands(), islands.len());
+    islands.islands
+}
+
+/// Island spanning the given rows and columns (inclusive).
+fn island(top: u32, left: u32, bottom: u32, right: u32) -> Island {
+    Island::new(Rect::new(left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Naive flood fill used as a reference for the parallel labeller.
+fn reference_islands(mask: &[bool], width: u32, height: u32, diagonal: bool) -> Vec<Island> {
+    let (w, h) = (width as usize, height as usize);
+    let mut seen = vec![false; mask.len()];
+    let mut out = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || seen[start] {
+            continue;
+        }
+        let (mut top, mut left, mut bottom, mut right) = (h, w, 0, 0);
+        let mut queue = VecDeque::from(vec![start]);
+        seen[start] = true;
+        while let Some(i) = queue.pop_front() {
+            let (x, y) = (i % w, i / w);
+            top = top.min(y);
+            left = left.min(x);
+            bottom = bottom.max(y);
+            right = right.max(x);
+            let mut push = |j: usize| {
+                if mask[j] && !seen[j] {
+                    seen[j] = true;
+                    queue.push_back(j);
+                }
+            };
+            if x > 0 {
+                push(i - 1);
+            }
+            if x + 1 < w {
+                push(i + 1);
+            }
+            if y > 0 {
+                push(i - w);
+            }
+            if y + 1 < h {
+                push(i + w);
+            }
+            if diagonal {
+                for &(dx, dy) in &[(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+                    let (nx, ny) = (x as isize + dx, y as isize + dy);
+                    if nx >= 0 && ny >= 0 && (nx as usize) < w && (ny as usize) < h {
+                        push(ny as usize * w + nx as usize);
+                    }
+                }
+            }
+        }
+        out.push(island(top as _, left as _, bottom as _, right as _));
+    }
+    out.sort_by_key(|island| {
+        (island.rect.y, island.rect.x, island.rect.bottom(), island.rect.right())
+    });
+    out
+}
+
+#[test]
+fn test_islands_solid_sprite() {
+    let (mask, w, h) = mask_from_str(&[
+        "..........",
+        "..######..",
+        "..######..",
+        "..######..",
+        "..######..",
+        "..........",
+    ]);
+    let islands = encoded_islands(&mask, w, h);
+    assert_eq!(islands, vec![island(1, 2, 4, 7)]);
+}
+
+#[test]
+fn test_islands_concave_shape() {
+    // the two arms are only joined in the last row
+    let (mask, w, h) = mask_from_str(&[
+        "#....#..#",
+        "#....#..#",
+        "#....#...",
+        "#....#...",
+        "######...",
+    ]);
+    let islands = encoded_islands(&mask, w, h);
+    assert_eq!(islands, vec![island(0, 0, 4, 5), island(0, 8, 1, 8)]);
+}
+
+#[test]
+fn test_islands_match_flood_fill() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for &(w, h) in &[(1, 1), (1, 97), (97, 1), (13, 200), (64, 64), (311, 257)] {
+        let mask = (0..w * h).map(|_| rng.gen_bool(0.45)).collect::<Vec<_>>();
+        assert_eq!(encoded_islands(&mask, w, h), reference_islands(&mask, w, h, false));
+        let eight = Islands::find_islands(&mask, w, h, Connectivity::Eight).unwrap();
+        assert_eq!(eight, reference_islands(&mask, w, h, true));
+    }
+}
+
+#[test]
+fn test_islands_empty_image() {
+    let (mask, w, h) = mask_from_str(&["....", "...."]);
+    assert!(encoded_islands(&mask, w, h).is_empty());
+}
+
+#[test]
+fn test_islands_encode_to_buf() {
+    // the island table fits in the space the pixel stream leaves over
+    let (w, h) = (20, 15);
+    let mask = (0..w * h).map(|i| i % 7 == 0 || i % 20 < 3).collect::<Vec<_>>();
+    let pixels = rgba_from_mask(&mask);
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    assert!(Decoder::new(&encoded).unwrap().header().n_islands() > 0);
+    let mut buf = vec![0; encode_max_len(w, h, 4)];
+    let n = encode_to_buf(&mut buf, &pixels, w, h, true).unwrap();
+    assert_eq!(&buf[..n], encoded.as_slice());
+    let encoder = Encoder::new(&pixels, w, h).unwrap();
+    let options = IslandOptions::new().with_stats(true).with_masks(true);
+    let mut encoder = encoder.with_island_options(options);
+    let mut buf = vec![0; encoder.required_buf_len()];
+    let n = encoder.encode_to_buf(&mut buf, true).unwrap();
+    assert_eq!(&buf[..n], encoder.encode_to_vec(true).unwrap().as_slice());
+
+    // a worst-case pixel stream leaves no room: the error reports the exact size
+    let pixels = (0..w * h).flat_map(|i| [i as u8, 1, 2, 254 + (i % 2) as u8]).collect::<Vec<_>>();
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    let mut buf = vec![0; encode_max_len(w, h, 4)];
+    match encode_to_buf(&mut buf, &pixels, w, h, true) {
+        Err(Error::OutputBufferTooSmall { size, required }) => {
+            assert_eq!((size, required), (buf.len(), encoded.len()));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    buf.resize(encoded.len(), 0);
+    assert_eq!(encode_to_buf(&mut buf, &pixels, w, h, true).unwrap(), encoded.len());
+    assert_eq!(buf, encoded);
+}
+
+#[test]
+fn test_islands_diagonal_line_connectivity() {
+    let (mask, w, h) = mask_from_str(&[
+        "#.....",
+        ".#....",
+        "..#...",
+        "...#..",
+        "....#.",
+        ".....#",
+    ]);
+    let four = Islands::find_islands(&mask, w, h, Connectivity::Four).unwrap();
+    assert_eq!(four.len(), 6);
+    let eight = Islands::find_islands(&mask, w, h, Connectivity::Eight).unwrap();
+    assert_eq!(eight, vec![island(0, 0, 5, 5)]);
+
+    // anti-diagonal strokes must merge as well
+    let (mask, w, h) = mask_from_str(&["...#", "..#.", ".#..", "#..."]);
+    let eight = Islands::find_islands(&mask, w, h, Connectivity::Eight).unwrap();
+    assert_eq!(eight, vec![island(0, 0, 3, 3)]);
+}
+
+#[test]
+fn test_islands_encoder_connectivity() {
+    let (mask, w, h) = mask_from_str(&["#...#", ".#.#.", "..#..", ".#.#.", "#...#"]);
+    let pixels = rgba_from_mask(&mask);
+    let encoded =
+        Encoder::new(&pixels, w, h).unwrap().with_connectivity(Connectivity::Eight).encode_to_vec(true);
+    let (_, decoded, islands) = decode_qoi(encoded.unwrap()).unwrap();
+    assert_eq!(decoded, pixels);
+    assert_eq!(islands.islands, vec![island(0, 0, 4, 4)]);
+    assert_eq!(encoded_islands(&mask, w, h).len(), 9);
+}
+
+#[test]
+fn test_islands_find_islands_invalid_mask() {
+    assert!(Islands::find_islands(&[true; 5], 2, 2, Connectivity::Four).is_err());
+    assert!(Islands::find_islands(&[], 0, 0, Connectivity::Four).is_err());
+}
+
+fn encoded_islands_with(
+    pixels: &[u8], width: u32, height: u32, foreground: Foreground,
+) -> Vec<Island> {
+    let options = IslandOptions::new().with_foreground(foreground);
+    let mut encoder = Encoder::new(pixels, width, height).unwrap().with_island_options(options);
+    let (_, decoded, islands) = decode_qoi(encoder.encode_to_vec(true).unwrap()).unwrap();
+    assert_eq!(decoded, pixels);
+    islands.islands
+}
+
+#[test]
+fn test_islands_foreground_alpha_threshold() {
+    // fully transparent, but non-black background with a faint halo around the sprite
+    let (w, h) = (6, 3);
+    let mut pixels = [[255, 255, 255, 0]; 18];
+    pixels[7] = [10, 20, 30, 255];
+    pixels[8] = [10, 20, 30, 255];
+    pixels[9] = [10, 20, 30, 8];
+    let pixels = pixels.concat();
+    assert_eq!(encoded_islands_with(&pixels, w, h, Foreground::NonZero).len(), 1);
+    let islands = encoded_islands_with(&pixels, w, h, Foreground::Alpha { threshold: 16 });
+    assert_eq!(islands, vec![island(1, 1, 1, 2)]);
+}
+
+#[test]
+fn test_islands_foreground_background_colour() {
+    // RGB image on a white background with slight noise
+    let (w, h) = (5, 4);
+    let mut pixels = [[255, 255, 255]; 20];
+    pixels[3] = [250, 255, 253];
+    pixels[11] = [0, 0, 0];
+    pixels[12] = [0, 0, 0];
+    pixels[16] = [0, 0, 0];
+    let pixels = pixels.concat();
+    let islands = encoded_islands_with(&pixels, w, h, Foreground::Background { tolerance: 8 });
+    assert_eq!(islands, vec![island(2, 1, 3, 2)]);
+}
+
+#[test]
+fn test_islands_foreground_chroma_key() {
+    let green = [0, 255, 0, 255];
+    let (w, h) = (4, 2);
+    let pixels = [green, [1, 250, 2, 255], [9, 9, 9, 255], green, green, green, green, [9, 9, 9, 9]];
+    let pixels = pixels.concat();
+    let chroma_key = Foreground::ChromaKey { color: green, tolerance: 5 };
+    let islands = encoded_islands_with(&pixels, w, h, chroma_key);
+    assert_eq!(islands, vec![island(0, 2, 0, 2), island(1, 3, 1, 3)]);
+}
+
+#[test]
+fn test_islands_foreground_custom() {
+    let (w, h) = (3, 3);
+    let pixels = (0..9_u8).flat_map(|i| [i * 20, 0, 0]).collect::<Vec<_>>();
+    let red = Foreground::custom(|px| px[0] >= 100);
+    let islands = encoded_islands_with(&pixels, w, h, red);
+    assert_eq!(islands, vec![island(1, 0, 2, 2)]);
+}
+
+#[test]
+fn test_islands_detect_matches_encoder() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let (w, h) = (120, 80);
+    let pixels = (0..w * h).flat_map(|_| [rng.gen_range(0..4) * 64, 0, 0]).collect::<Vec<u8>>();
+    let options = IslandOptions::new()
+        .with_connectivity(Connectivity::Eight)
+        .with_foreground(Foreground::Background { tolerance: 70 });
+    let detected = Islands::detect(&pixels, w, h, Channels::Rgb, &options).unwrap();
+    assert!(!detected.is_empty());
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let (_, _, islands) = decode_qoi(encoder.encode_to_vec(true).unwrap()).unwrap();
+    assert_eq!(detected, islands.islands);
+}
+
+#[test]
+fn test_islands_detect_invalid_length() {
+    let options = IslandOptions::new();
+    assert!(Islands::detect([0_u8; 12], 2, 2, Channels::Rgb, &options).is_ok());
+    assert!(Islands::detect([0_u8; 12], 2, 2, Channels::Rgba, &options).is_err());
+    assert!(Islands::detect([0_u8; 0], 0, 2, Channels::Rgb, &options).is_err());
+}
+
+#[test]
+fn test_islands_raster_order() {
+    let (mask, w, h) = mask_from_str(&[
+        "....##",
+        "#...##",
+        "#.....",
+        "..#..#",
+        "..#..#",
+    ]);
+    let islands = encoded_islands(&mask, w, h);
+    let expected = vec![island(0, 4, 1, 5), island(1, 0, 2, 0), island(3, 2, 4, 2), island(3, 5, 4, 5)];
+    assert_eq!(islands, expected);
+}
+
+#[test]
+fn test_islands_reproducible_across_thread_counts() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let (w, h) = (257, 193);
+    let mask = (0..w * h).map(|_| rng.gen_bool(0.4)).collect::<Vec<_>>();
+    let pixels = rgba_from_mask(&mask);
+    let encode = |threads| {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| encode_to_vec(&pixels, w, h, true).unwrap())
+    };
+    let expected = encode(1);
+    for &threads in &[2, 3, 8, 13] {
+        assert_eq!(encode(threads), expected);
+    }
+}
+
+#[test]
+fn test_rect_geometry() {
+    let a = Rect::new(2, 3, 4, 5);
+    assert_eq!((a.right(), a.bottom(), a.area()), (6, 8, 20));
+    assert!(a.contains(2, 3) && a.contains(5, 7));
+    assert!(!a.contains(6, 3) && !a.contains(2, 8) && !a.contains(1, 3));
+
+    let b = Rect::new(5, 7, 3, 3);
+    assert!(a.intersects(&b));
+    assert_eq!(a.intersection(&b), Some(Rect::new(5, 7, 1, 1)));
+    assert_eq!(a.union(&b), Rect::new(2, 3, 6, 7));
+    assert_eq!(a.union(&Rect::default()), a);
+
+    // touching edges don't intersect since right/bottom edges are exclusive
+    let c = Rect::new(6, 3, 2, 2);
+    assert!(!a.intersects(&c));
+    assert_eq!(a.intersection(&c), None);
+}
+
+#[test]
+fn test_rect_clip_to_header() {
+    let encoded = encode_to_vec([0_u8; 10 * 6 * 3], 10, 6, false).unwrap();
+    let header = qoi::decode_header(&encoded).unwrap();
+    assert_eq!(Rect::new(8, 4, 5, 5).clip_to(&header), Some(Rect::new(8, 4, 2, 2)));
+    assert_eq!(Rect::new(0, 0, 10, 6).clip_to(&header), Some(Rect::new(0, 0, 10, 6)));
+    assert_eq!(Rect::new(10, 0, 1, 1).clip_to(&header), None);
+}
+
+#[test]
+fn test_islands_stats() {
+    let (w, h) = (4, 3);
+    let mut pixels = [[0_u8; 4]; 12];
+    pixels[1] = [200, 10, 0, 255];
+    pixels[2] = [100, 20, 0, 128];
+    pixels[5] = [0, 30, 50, 255];
+    pixels[11] = [7, 7, 7, 7];
+    let pixels = pixels.concat();
+
+    let options = IslandOptions::new().with_stats(true);
+    let detected = Islands::detect(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    let expected = vec![
+        IslandStats {
+            pixel_count: 3,
+            centroid: (4. / 3., 1. / 3.),
+            mean_color: [100, 20, 17],
+            mean_alpha: 213,
+        },
+        IslandStats { pixel_count: 1, centroid: (3., 2.), mean_color: [7, 7, 7], mean_alpha: 7 },
+    ];
+    assert_eq!(detected.iter().map(|island| island.stats.unwrap()).collect::<Vec<_>>(), expected);
+    assert!(Islands::detect(&pixels, w, h, Channels::Rgba, &IslandOptions::new())
+        .unwrap()
+        .iter()
+        .all(|island| island.stats.is_none()));
+
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let (header, decoded, islands) = decode_qoi(encoder.encode_to_vec(true).unwrap()).unwrap();
+    assert!(header.has_island_stats());
+    // islands with statistics can still be keyed on
+    let set = detected.iter().chain(islands.iter()).collect::<HashSet<_>>();
+    assert_eq!(set.len(), 2);
+    assert_eq!(decoded, pixels);
+    assert_eq!(islands.islands, detected);
+}
+
+#[test]
+fn test_islands_stats_rgb_alpha() {
+    let pixels = [[0, 0, 0], [10, 20, 30], [30, 20, 10]].concat();
+    let options = IslandOptions::new().with_stats(true);
+    let mut encoder = Encoder::new(&pixels, 3, 1).unwrap().with_island_options(options.clone());
+    let (header, _, islands) = decode_qoi(encoder.encode_to_vec(true).unwrap()).unwrap();
+    assert!(header.has_island_stats());
+    let stats = islands.islands[0].stats.unwrap();
+    assert_eq!((stats.pixel_count, stats.centroid), (2, (1.5, 0.)));
+    assert_eq!((stats.mean_color, stats.mean_alpha), ([20, 20, 20], 255));
+
+    // without islands, no optional fields are flagged
+    let mut encoder = Encoder::new(&pixels, 3, 1).unwrap().with_island_options(options);
+    let (header, _, islands) = decode_qoi(encoder.encode_to_vec(false).unwrap()).unwrap();
+    assert!(!header.has_island_stats());
+    assert!(islands.is_empty());
+}
+
+#[test]
+fn test_islands_masks() {
+    // the dot lies inside the bounding box of the concave shape
+    let (mask, w, h) = mask_from_str(&[
+        "#....",
+        "#..#.",
+        "#....",
+        "#####",
+    ]);
+    let pixels = rgba_from_mask(&mask);
+    let options = IslandOptions::new().with_masks(true);
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let encoded = encoder.encode_to_vec(true).unwrap();
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert!(decoder.header().has_island_masks());
+    assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+    let masks = decoder.island_masks().unwrap();
+    let islands = decoder.islands().unwrap();
+    assert_eq!(islands.islands, vec![island(0, 0, 3, 4), island(1, 3, 1, 3)]);
+    assert_eq!(masks.len(), 2);
+    assert_eq!(masks[0].rect(), islands.islands[0].rect);
+    assert_eq!((masks[0].count(), masks[1].count()), (8, 1));
+    assert!(masks[0].contains(0, 0) && masks[0].contains(4, 3));
+    assert!(!masks[0].contains(3, 1) && masks[1].contains(3, 1));
+    assert!(!masks[1].contains(0, 0) && !masks[0].contains(5, 3));
+
+    // every foreground pixel belongs to exactly one mask
+    for y in 0..h {
+        for x in 0..w {
+            let n = masks.iter().filter(|m| m.contains(x, y)).count();
+            assert_eq!(n, mask[(y * w + x) as usize] as usize);
+        }
+    }
+
+    let expected = mask_from_str(&["#....", "#....", "#....", "#####"]).0;
+    assert_eq!(masks[0].to_vec(), expected);
+    assert_eq!(islands.mask(1), Some(&masks[1]));
+}
+
+#[test]
+fn test_islands_without_masks() {
+    let (mask, w, h) = mask_from_str(&["#.#", "..."]);
+    let encoded = encode_to_vec(rgba_from_mask(&mask), w, h, true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert!(!decoder.header().has_island_masks());
+    decoder.decode_to_vec().unwrap();
+    assert!(decoder.island_masks().unwrap().is_empty());
+    assert_eq!(decoder.islands().unwrap().len(), 2);
+}
+
+#[test]
+fn test_islands_without_decoding_pixels() {
+    let (mask, w, h) = mask_from_str(&[
+        "##..#",
+        ".....",
+        "#..##",
+    ]);
+    let pixels = rgba_from_mask(&mask);
+    let options = IslandOptions::new().with_stats(true).with_masks(true);
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let encoded = encoder.encode_to_vec(true).unwrap();
+    let (header, decoded, expected) = decode_qoi(&encoded).unwrap();
+    assert_eq!(decoded, pixels);
+    assert_eq!(expected.len(), 4);
+
+    // the pixel stream is immediately followed by the island table, and then by the
+    // extension section holding the flags
+    let extension = 22 + header.n_encode() + 16 * header.n_islands();
+    assert_eq!(&encoded[extension..extension + 4], b"qoix");
+    assert_eq!(encoded[extension + 4], header.flags);
+    assert_eq!(decode_islands(&encoded).unwrap(), expected);
+
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.islands().unwrap(), expected);
+    assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+    assert_eq!(decoder.islands().unwrap(), expected);
+    #[allow(deprecated)]
+    let islands = decoder.decode_to_islands().unwrap();
+    assert_eq!(islands, expected);
+
+    // the pixel stream must be present in full even if it's skipped
+    let truncated = &encoded[..22 + header.n_encode() - 1];
+    assert!(decode_islands(truncated).is_err());
+}
+
+fn crop(pixels: &[u8], width: u32, channels: usize, rect: Rect) -> Vec<u8> {
+    (rect.y..rect.bottom())
+        .flat_map(|y| {
+            let row = (y * width) as usize;
+            pixels[(row + rect.x as usize) * channels..(row + rect.right() as usize) * channels]
+                .iter()
+                .copied()
+        })
+        .collect()
+}
+
+#[test]
+fn test_decode_region() {
+    let mut rng = StdRng::seed_from_u64(10);
+    let (w, h) = (37, 23);
+    for &channels in &[Channels::Rgb, Channels::Rgba] {
+        let n = channels.as_u8() as usize;
+        // runs of solid colour mixed with noise, so that all ops are exercised
+        let mut pixels = Vec::new();
+        while pixels.len() < (w * h) as usize * n {
+            let px: Vec<u8> = (0..n).map(|_| rng.gen::<u8>() & 0xf0).collect();
+            for _ in 0..rng.gen_range(1..80) {
+                pixels.extend_from_slice(&px);
+            }
+        }
+        pixels.truncate((w * h) as usize * n);
+        let encoded = Encoder::new(&pixels, w, h).unwrap().encode_to_vec(false).unwrap();
+        let mut decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgba);
+        let rgba = decoder.decode_to_vec().unwrap();
+
+        for _ in 0..50 {
+            let (x, y) = (rng.gen_range(0..w), rng.gen_range(0..h));
+            let rect = Rect::new(x, y, rng.gen_range(1..=w - x), rng.gen_range(1..=h - y));
+            let mut decoder = Decoder::new(&encoded).unwrap();
+            assert_eq!(decoder.decode_region_to_vec(rect).unwrap(), crop(&pixels, w, n, rect));
+            let mut decoder = decoder.with_channels(Channels::Rgba);
+            assert_eq!(decoder.decode_region_to_vec(rect).unwrap(), crop(&rgba, w, 4, rect));
+        }
+
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        let full = Rect::new(0, 0, w, h);
+        assert_eq!(decoder.decode_region_to_vec(full).unwrap(), pixels);
+        // the stream isn't consumed by region decoding
+        assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+        assert_eq!(decoder.decode_region_to_vec(full).unwrap(), pixels);
+    }
+}
+
+#[test]
+fn test_decode_region_of_island() {
+    let (mask, w, h) = mask_from_str(&[
+        "......",
+        "..##..",
+        "..###.",
+        "......",
+    ]);
+    let pixels = rgba_from_mask(&mask);
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    let rect = decoder.islands().unwrap().islands[0].rect;
+    let mut buf = vec![0; decoder.required_region_len(rect)];
+    assert_eq!(decoder.decode_region(rect, &mut buf).unwrap(), 6 * 4);
+    assert_eq!(buf, crop(&pixels, w, 4, rect));
+}
+
+#[test]
+fn test_decode_region_invalid() {
+    let pixels = vec![0_u8; 4 * 3 * 4];
+    let encoded = encode_to_vec(&pixels, 4, 3, false).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    for &rect in &[Rect::new(0, 0, 0, 1), Rect::new(3, 0, 2, 1), Rect::new(0, 2, 1, 2)] {
+        assert!(matches!(decoder.decode_region(rect, [0; 64]), Err(Error::InvalidRegion { .. })));
+    }
+    let res = decoder.decode_region(Rect::new(1, 1, 2, 2), [0; 15]);
+    assert!(matches!(res, Err(Error::OutputBufferTooSmall { size: 15, required: 16 })));
+}
+
+/// Random image with a few noisy rectangular islands on a transparent background.
+fn noisy_islands_image(rng: &mut StdRng, w: u32, h: u32) -> Vec<u8> {
+    let mut pixels = vec![0_u8; (w * h * 4) as usize];
+    for _ in 0..6 {
+        let (x, y) = (rng.gen_range(0..w), rng.gen_range(0..h));
+        let (rw, rh) = (rng.gen_range(1..=(w - x).min(9)), rng.gen_range(1..=(h - y).min(9)));
+        for row in y..y + rh {
+            for col in x..x + rw {
+                let i = ((row * w + col) * 4) as usize;
+                let c = rng.gen::<u8>() | 1;
+                pixels[i..i + 4].copy_from_slice(&[c, c / 2, 255 - c, 255]);
+            }
+        }
+    }
+    pixels
+}
+
+#[test]
+fn test_island_streams() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let (w, h) = (40, 30);
+    let pixels = noisy_islands_image(&mut rng, w, h);
+    let options = IslandOptions::new().with_stats(true).with_masks(true).with_streams(true);
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options.clone());
+    let mut encoded = encoder.encode_to_vec(true).unwrap();
+
+    let (header, decoded, islands) = decode_qoi(&encoded).unwrap();
+    assert!(header.has_island_streams());
+    assert_eq!(decoded, pixels);
+    assert_eq!(islands.islands, Islands::detect(&pixels, w, h, Channels::Rgba, &options).unwrap());
+    assert!(!islands.is_empty());
+
+    // wipe the main pixel stream: the islands must still decode from their own sub-streams
+    encoded[22..22 + header.n_encode()].iter_mut().for_each(|b| *b = 0);
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    let expected = islands.iter().map(|isl| crop(&pixels, w, 4, isl.rect)).collect::<Vec<_>>();
+    for (i, expected) in expected.iter().enumerate() {
+        assert_eq!(&decoder.decode_island_to_vec(i).unwrap(), expected);
+    }
+    assert_eq!(decoder.decode_all_islands().unwrap(), expected);
+
+    let mut decoder = decoder.with_channels(Channels::Rgb);
+    let rgb = pixels.chunks(4).flat_map(|px| px[..3].to_vec()).collect::<Vec<_>>();
+    let rect = islands.islands[0].rect;
+    assert_eq!(decoder.decode_island_to_vec(0).unwrap(), crop(&rgb, w, 3, rect));
+}
+
+#[test]
+fn test_island_streams_fallback() {
+    let mut rng = StdRng::seed_from_u64(12);
+    let (w, h) = (25, 20);
+    let pixels = noisy_islands_image(&mut rng, w, h);
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert!(!decoder.header().has_island_streams());
+    let islands = decoder.islands().unwrap();
+    let expected = islands.iter().map(|isl| crop(&pixels, w, 4, isl.rect)).collect::<Vec<_>>();
+    for (i, expected) in expected.iter().enumerate() {
+        assert_eq!(&decoder.decode_island_to_vec(i).unwrap(), expected);
+    }
+    assert_eq!(decoder.decode_all_islands().unwrap(), expected);
+
+    let n_islands = islands.len();
+    let res = decoder.decode_island_to_vec(n_islands);
+    assert!(matches!(res, Err(Error::InvalidIslandIndex { index, .. }) if index == n_islands));
+}
+
+#[test]
+fn test_sparse() {
+    let mut rng = StdRng::seed_from_u64(13);
+    let (w, h) = (300, 200);
+    let pixels = noisy_islands_image(&mut rng, w, h);
+    let dense = encode_to_vec(&pixels, w, h, true).unwrap();
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_sparse([0; 4]);
+    let sparse = encoder.encode_to_vec(false).unwrap();
+    assert!(encoder.header().is_sparse() && encoder.header().has_island_streams());
+    assert_eq!(encoder.header().n_encode(), 4);
+    assert!(sparse.len() < dense.len());
+    let mut buf = vec![0; encoder.required_buf_len()];
+    let n = encoder.encode_to_buf(&mut buf, false).unwrap();
+    assert_eq!(&buf[..n], sparse.as_slice());
+
+    let (header, decoded, islands) = decode_qoi(&sparse).unwrap();
+    assert!(header.is_sparse());
+    assert_eq!(decoded, pixels);
+    assert_eq!(islands, decode_islands(&dense).unwrap());
+
+    let mut decoder = Decoder::new(&sparse).unwrap();
+    assert_eq!(decoder.background(), Some([0; 4]));
+    assert_eq!(Decoder::new(&dense).unwrap().background(), None);
+    for island in &islands {
+        let rect = Rect::new(island.rect.x.saturating_sub(2), island.rect.y, 7, 3).clip_to(&header);
+        let rect = rect.unwrap();
+        assert_eq!(decoder.decode_region_to_vec(rect).unwrap(), crop(&pixels, w, 4, rect));
+    }
+    let mut decoder = decoder.with_channels(Channels::Rgb);
+    let rgb = pixels.chunks(4).flat_map(|px| px[..3].to_vec()).collect::<Vec<_>>();
+    assert_eq!(decoder.decode_to_vec().unwrap(), rgb);
+
+    // the island options can't make it lossy: a stray pixel is kept even though it's
+    // below the minimum area, too faint for the predicate and beyond the island cap
+    let mut pixels = pixels;
+    pixels[..4].copy_from_slice(&[1, 0, 0, 0]);
+    let options = IslandOptions::new()
+        .with_foreground(Foreground::Alpha { threshold: 0 })
+        .with_min_area(2)
+        .with_max_islands(Some(1));
+    let encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let sparse = encoder.with_sparse([0; 4]).encode_to_vec(true).unwrap();
+    let (_, decoded, islands) = decode_qoi(&sparse).unwrap();
+    assert_eq!(decoded, pixels);
+    assert!(islands.len() > 1);
+    assert_eq!(islands.as_slice()[0].rect.x, 0);
+}
+
+#[test]
+fn test_sparse_background() {
+    // pixels differing from the background colour are kept, whatever the predicate
+    let (w, h) = (4, 3);
+    let mut pixels = [[10, 20, 30], [10, 20, 30], [10, 20, 30], [10, 20, 31]].repeat(3).concat();
+    pixels[15..18].copy_from_slice(&[200, 0, 0]);
+    let foreground = Foreground::Background { tolerance: 5 };
+    let options = IslandOptions::new().with_foreground(foreground);
+    let encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let mut encoder = encoder.with_sparse([10, 20, 30, 0]);
+    let encoded = encoder.encode_to_vec(true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.islands().unwrap().islands, vec![island(0, 3, 2, 3), island(1, 1, 1, 1)]);
+    assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgba);
+    assert_eq!(&decoder.decode_to_vec().unwrap()[..4], &[10, 20, 30, 255]);
+}
+
+#[test]
+fn test_invalid_island_table() {
+    let (mask, w, h) = mask_from_str(&[
+        "#...",
+        "..##",
+    ]);
+    let encoded = encode_to_vec(rgba_from_mask(&mask), w, h, true).unwrap();
+    let header = *Decoder::new(&encoded).unwrap().header();
+    let table = 22 + header.n_encode();
+    let patched = |offset: usize, value: u32| {
+        let mut data = encoded.clone();
+        data[table + offset..table + offset + 4].copy_from_slice(&value.to_be_bytes());
+        data
+    };
+
+    // second record: top, left, bottom, right = 1, 2, 1, 3 (inclusive)
+    let record = [1_u32, 2, 1, 3].iter().flat_map(|v| v.to_be_bytes().to_vec()).collect::<Vec<_>>();
+    assert_eq!(&encoded[table + 16..table + 32], record.as_slice());
+    assert!(decode_islands(patched(20, 3)).is_ok());
+    let invalid = [(16, 2), (20, 4), (24, 0), (24, 2), (28, 1), (28, 4), (28, u32::MAX)];
+    for &(offset, value) in &invalid {
+        let res = decode_islands(patched(offset, value));
+        assert!(matches!(res, Err(Error::InvalidIsland { index: 1, .. })), "{:?}", res);
+        let res = Decoder::new(&patched(offset, value)).unwrap().decode_island_to_vec(1);
+        assert!(matches!(res, Err(Error::InvalidIsland { index: 1, .. })), "{:?}", res);
+    }
+
+    // claim a third island, the table only has room for two (plus the padding)
+    let mut data = encoded.clone();
+    data[16..20].copy_from_slice(&3_u32.to_be_bytes());
+    data.truncate(table + 2 * 16);
+    let res = decode_islands(&data);
+    assert!(matches!(res, Err(Error::TruncatedIslandTable { expected: 48, found: 32 })));
+    data.truncate(table + 20);
+    let res = Decoder::new(&data).unwrap().decode_island_to_vec(0);
+    assert!(matches!(res, Err(Error::TruncatedIslandTable { expected: 48, found: 20 })));
+}
+
+#[test]
+fn test_island_format_compatibility() {
+    let (mask, w, h) = mask_from_str(&[
+        "##..",
+        "...#",
+    ]);
+    let pixels = rgba_from_mask(&mask);
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    let (header, _, islands) = decode_qoi(&encoded).unwrap();
+    let (n_stream, table_len) = (header.n_encode(), 16 * header.n_islands());
+    assert_eq!(header.flags, 0);
+    assert_eq!(encoded.len(), 22 + n_stream + table_len + 8);
+
+    // optional island data goes after the island table, which stays in place for readers
+    // that don't know about it
+    let options = IslandOptions::new().with_stats(true).with_masks(true).with_streams(true);
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let extended = encoder.encode_to_vec(true).unwrap();
+    assert_eq!(extended[..22 + n_stream + table_len], encoded[..22 + n_stream + table_len]);
+    assert_eq!(&extended[22 + n_stream + table_len..][..4], b"qoix");
+    let (extended_header, decoded, extended_islands) = decode_qoi(&extended).unwrap();
+    assert_eq!(extended_header, header.with_flags(encoder.header().flags));
+    assert_eq!(decoded, pixels);
+    assert!(extended_islands.iter().all(|island| island.stats.is_some()));
+    let rects = |islands: &Islands| islands.iter().map(|island| island.rect).collect::<Vec<_>>();
+    assert_eq!(rects(&extended_islands), rects(&islands));
+
+    // images written before the extension section count the island table and the padding
+    // in the size of the pixel stream
+    let mut legacy = encoded.clone();
+    legacy[12..16].copy_from_slice(&((encoded.len() - 22) as u32).to_be_bytes());
+    let (legacy_header, decoded, legacy_islands) = decode_qoi(&legacy).unwrap();
+    assert_eq!(legacy_header, header);
+    assert_eq!(decoded, pixels);
+    assert_eq!(legacy_islands, islands);
+    assert_eq!(decode_islands(&legacy).unwrap(), islands);
+    let mut decoder = Decoder::new(&legacy).unwrap();
+    assert_eq!(decoder.decode_island_to_vec(1).unwrap(), [200, 100, 50, 255]);
+
+    // a current image missing its padding is truncated, not legacy
+    let plain = encode_to_vec(&pixels, w, h, false).unwrap();
+    for image in &[&encoded, &plain] {
+        let truncated = &image[..image.len() - 8];
+        let decoder = Decoder::new(truncated).unwrap();
+        assert_eq!(decoder.header(), Decoder::new(image).unwrap().header());
+        assert!(matches!(decode_qoi(truncated), Err(Error::UnexpectedBufferEnd)));
+    }
+    let mut legacy = plain.clone();
+    legacy[12..16].copy_from_slice(&((plain.len() - 22) as u32).to_be_bytes());
+    assert_eq!(decode_qoi(&legacy).unwrap().1, pixels);
+}
+
+fn detect_with(rows: &[&str], options: &IslandOptions) -> Vec<Island> {
+    let (mask, w, h) = mask_from_str(rows);
+    let pixels = rgba_from_mask(&mask);
+    let islands = Islands::detect(&pixels, w, h, Channels::Rgba, options).unwrap();
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options.clone());
+    let encoded = encoder.encode_to_vec(true).unwrap();
+    assert_eq!(decode_islands(&encoded).unwrap().islands, islands);
+    islands
+}
+
+#[test]
+fn test_islands_min_area_and_padding() {
+    let rows = [
+        "#.....",
+        "...##.",
+        "...##.",
+        "......",
+        ".....#",
+    ];
+    let options = IslandOptions::new().with_min_area(2);
+    assert_eq!(detect_with(&rows, &options), vec![island(1, 3, 2, 4)]);
+    let options = options.with_min_area(5);
+    assert_eq!(detect_with(&rows, &options), vec![]);
+
+    // padded boxes are clamped to the image
+    let options = IslandOptions::new().with_padding(2);
+    let expected = vec![island(0, 0, 2, 2), island(0, 1, 4, 5), island(2, 3, 4, 5)];
+    assert_eq!(detect_with(&rows, &options), expected);
+}
+
+#[test]
+fn test_islands_merge() {
+    let rows = [
+        "##.##...#",
+        ".........",
+        ".........",
+        "##.......",
+    ];
+    let all = vec![island(0, 0, 0, 1), island(0, 3, 0, 4), island(0, 8, 0, 8), island(3, 0, 3, 1)];
+    assert_eq!(detect_with(&rows, &IslandOptions::new().with_merge_distance(Some(0))), all);
+    assert_eq!(detect_with(&rows, &IslandOptions::new().with_merge_distance(Some(1))), all);
+
+    let options = IslandOptions::new().with_merge_distance(Some(2));
+    let expected = vec![island(0, 0, 0, 4), island(0, 8, 0, 8), island(3, 0, 3, 1)];
+    assert_eq!(detect_with(&rows, &options), expected);
+
+    // merging cascades: the merged box gets close enough to the ones further away
+    let options = IslandOptions::new().with_merge_distance(Some(3));
+    assert_eq!(detect_with(&rows, &options), vec![island(0, 0, 3, 4), island(0, 8, 0, 8)]);
+
+    // padded boxes that overlap are merged even with a zero distance, touching ones aren't
+    let options = IslandOptions::new().with_padding(1).with_merge_distance(Some(0));
+    let expected = vec![island(0, 0, 1, 5), island(0, 7, 1, 8), island(2, 0, 3, 2)];
+    assert_eq!(detect_with(&rows, &options), expected);
+
+    // stats and masks cover all of the merged components
+    let (mask, w, h) = mask_from_str(&rows);
+    let options = IslandOptions::new().with_merge_distance(Some(2));
+    let options = options.with_stats(true).with_masks(true);
+    let pixels = rgba_from_mask(&mask);
+    let islands = Islands::detect(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    assert_eq!(islands[0].stats.unwrap().pixel_count, 4);
+    assert_eq!(islands[0].stats.unwrap().centroid, (2.0, 0.0));
+    let encoder = Encoder::new(&pixels, w, h).unwrap();
+    let encoded = encoder.with_island_options(options).encode_to_vec(true).unwrap();
+    let masks = Decoder::new(&encoded).unwrap().island_masks().unwrap();
+    assert_eq!(masks[0].to_vec(), vec![true, true, false, true, true]);
+}
+
+#[test]
+fn test_islands_max_count() {
+    let rows = [
+        "#..##..###",
+        "..........",
+        "##.......#",
+    ];
+    // of the two islands of size 2, the one coming first in raster order is kept
+    let options = IslandOptions::new().with_max_islands(Some(2));
+    assert_eq!(detect_with(&rows, &options), vec![island(0, 3, 0, 4), island(0, 7, 0, 9)]);
+    let options = IslandOptions::new().with_max_islands(Some(0));
+    assert_eq!(detect_with(&rows, &options), vec![]);
+    let options = IslandOptions::new().with_max_islands(Some(10));
+    assert_eq!(detect_with(&rows, &options).len(), 5);
+}
+
+fn contours_of(rows: &[&str], options: &IslandOptions) -> Vec<Vec<Contour>> {
+    let (mask, w, h) = mask_from_str(rows);
+    let options = options.clone().with_contours(true);
+    Islands::from_pixels(rgba_from_mask(&mask), w, h, Channels::Rgba, &options).unwrap().contours
+}
+
+#[test]
+fn test_island_contours() {
+    let outer = |points: &[(u32, u32)]| Contour { points: points.to_vec(), hole: false };
+    let contours = contours_of(&["...", ".#."], &IslandOptions::new());
+    assert_eq!(contours, vec![vec![outer(&[(1, 1), (2, 1), (2, 2), (1, 2)])]]);
+
+    let contours = contours_of(&["###", "#.#", "###"], &IslandOptions::new());
+    let hole = Contour { points: vec![(1, 1), (1, 2), (2, 2), (2, 1)], hole: true };
+    assert_eq!(contours, vec![vec![outer(&[(0, 0), (3, 0), (3, 3), (0, 3)]), hole]]);
+
+    // a single boundary goes around diagonal neighbours with 8-connectivity
+    let rows = ["#.", ".#"];
+    let options = IslandOptions::new().with_connectivity(Connectivity::Eight);
+    let contours = contours_of(&rows, &options);
+    let expected = outer(&[(0, 0), (1, 0), (1, 1), (2, 1), (2, 2), (1, 2), (1, 1), (0, 1)]);
+    assert_eq!(contours, vec![vec![expected]]);
+    // ... while with 4-connectivity, they're kept apart
+    let (mask, w, h) = mask_from_str(&rows);
+    let options = options.with_masks(true);
+    let islands = Islands::from_pixels(rgba_from_mask(&mask), w, h, Channels::Rgba, &options);
+    let contours = islands.unwrap().masks[0].contours(Connectivity::Four, 0.);
+    assert_eq!(contours.len(), 2);
+    assert!(contours.iter().all(|c| c.points.len() == 4 && !c.hole));
+}
+
+#[test]
+fn test_island_contours_hit_testing() {
+    let mut rng = StdRng::seed_from_u64(15);
+    let (w, h) = (24, 17);
+    for &connectivity in &[Connectivity::Four, Connectivity::Eight] {
+        let mask = (0..w * h).map(|_| rng.gen_bool(0.55)).collect::<Vec<_>>();
+        let options = IslandOptions::new().with_connectivity(connectivity).with_masks(true);
+        let options = options.with_contours(true).with_merge_distance(Some(2));
+        let islands =
+            Islands::from_pixels(rgba_from_mask(&mask), w, h, Channels::Rgba, &options).unwrap();
+        for (i, island) in islands.iter().enumerate() {
+            let contours = islands.contours(i).unwrap();
+            assert!(contours.iter().any(|c| !c.hole));
+            let mask = islands.mask(i).unwrap();
+            for y in island.rect.y..island.rect.bottom() {
+                for x in island.rect.x..island.rect.right() {
+                    let (cx, cy) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+                    let n = contours.iter().filter(|c| c.contains(cx, cy)).count();
+                    assert_eq!(n % 2 == 1, mask.contains(x, y), "{:?} {} {}", connectivity, x, y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_island_contours_simplified() {
+    let rows = [
+        "#.......",
+        "##......",
+        "###.....",
+        "####....",
+        "#####...",
+        "######..",
+    ];
+    let exact = contours_of(&rows, &IslandOptions::new());
+    assert_eq!(exact[0][0].points.len(), 14);
+    let simplified = contours_of(&rows, &IslandOptions::new().with_contour_tolerance(1.));
+    assert_eq!(simplified[0][0].points, vec![(0, 0), (6, 6), (0, 6)]);
+    assert!(simplified[0][0].points.iter().all(|p| exact[0][0].points.contains(p)));
+    let collapsed = contours_of(&["##", "##"], &IslandOptions::new().with_contour_tolerance(5.));
+    assert_eq!(collapsed[0][0].points.len(), 3);
+}
+
+#[test]
+fn test_island_contours_encoded() {
+    let mut rng = StdRng::seed_from_u64(16);
+    let (w, h) = (30, 20);
+    let pixels = noisy_islands_image(&mut rng, w, h);
+    for &streams in &[false, true] {
+        let options = IslandOptions::new().with_contours(true).with_streams(streams);
+        let options = options.with_masks(true).with_stats(true);
+        let expected = Islands::from_pixels(&pixels, w, h, Channels::Rgba, &options).unwrap();
+        let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+        let encoded = encoder.encode_to_vec(true).unwrap();
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        assert!(decoder.header().has_island_contours());
+        assert_eq!(decoder.islands().unwrap(), expected);
+        assert_eq!(decoder.island_contours().unwrap(), expected.contours);
+        assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+    }
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    assert!(Decoder::new(&encoded).unwrap().island_contours().unwrap().is_empty());
+}
+
+#[test]
+fn test_islands_labels() {
+    let (mask, w, h) = mask_from_str(&[
+        "##..#",
+        "#...#",
+        "..#..",
+        "##.##",
+    ]);
+    let pixels = rgba_from_mask(&mask);
+    let options = IslandOptions::new().with_labels(true).with_min_area(2);
+    let islands = Islands::from_pixels(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    #[rustfmt::skip]
+    let expected = vec![
+        1, 1, 0, 0, 2,
+        1, 0, 0, 0, 2,
+        0, 0, 0, 0, 0,
+        3, 3, 0, 4, 4,
+    ];
+    assert_eq!(islands.len(), 4);
+    assert_eq!(islands.labels, expected);
+
+    // merged islands share a label; the encoder returns the same islands it stores
+    let options = options.with_merge_distance(Some(2)).with_masks(true);
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options.clone());
+    let (encoded, islands) = encoder.encode_to_vec_with_islands(true).unwrap();
+    assert_eq!(islands, Islands::from_pixels(&pixels, w, h, Channels::Rgba, &options).unwrap());
+    assert_eq!(decode_islands(&encoded).unwrap().islands, islands.islands);
+    assert!(decode_islands(&encoded).unwrap().labels.is_empty());
+    assert_eq!(islands.len(), 1);
+    let expected = expected.iter().map(|&l| u32::from(l != 0)).collect::<Vec<_>>();
+    assert_eq!(islands.labels, expected);
+
+    // labels agree with the masks
+    let mut rng = StdRng::seed_from_u64(18);
+    let (w, h) = (40, 30);
+    let pixels = noisy_islands_image(&mut rng, w, h);
+    let options = IslandOptions::new().with_labels(true).with_masks(true);
+    let islands = Islands::from_pixels(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    for y in 0..h {
+        for x in 0..w {
+            let label = islands.labels[(y * w + x) as usize] as usize;
+            let owner = islands.masks.iter().position(|m| m.contains(x, y)).map_or(0, |i| i + 1);
+            assert_eq!(label, owner);
+        }
+    }
+    let islands = Islands::from_pixels(&pixels, w, h, Channels::Rgba, &IslandOptions::new());
+    assert!(islands.unwrap().labels.is_empty());
+}
+
+#[test]
+fn test_islands_diff() {
+    let mut rng = StdRng::seed_from_u64(19);
+    let (w, h) = (32, 24);
+    let prev = (0..w * h * 3).map(|_| rng.gen::<u8>()).collect::<Vec<_>>();
+    let mut next = prev.clone();
+    let mut touch = |x: u32, y: u32, c: usize| next[(y * w + x) as usize * 3 + c] ^= 1;
+    for x in 2..6 {
+        touch(x, 1, x as usize % 3);
+    }
+    touch(20, 10, 2);
+    touch(21, 11, 0);
+    let options = IslandOptions::new().with_labels(true);
+    let diff = Islands::diff(&prev, &next, w, h, Channels::Rgb, &options).unwrap();
+    let expected = vec![island(1, 2, 1, 5), island(10, 20, 10, 20), island(11, 21, 11, 21)];
+    assert_eq!(diff.islands, expected);
+    assert_eq!(diff.labels.iter().filter(|&&l| l != 0).count(), 6);
+
+    // the foreground predicate doesn't matter, everything else applies as usual
+    let options = IslandOptions::new()
+        .with_foreground(Foreground::Alpha { threshold: 255 })
+        .with_connectivity(Connectivity::Eight)
+        .with_stats(true);
+    let diff = Islands::diff(&prev, &next, w, h, Channels::Rgb, &options).unwrap();
+    assert_eq!(diff.iter().map(|i| i.rect).collect::<Vec<_>>(), vec![
+        Rect::new(2, 1, 4, 1),
+        Rect::new(20, 10, 2, 2),
+    ]);
+    assert_eq!(diff.islands[1].stats.unwrap().pixel_count, 2);
+
+    assert!(Islands::diff(&prev, &prev, w, h, Channels::Rgb, &options).unwrap().is_empty());
+    assert!(matches!(
+        Islands::diff(&prev, &next[1..], w, h, Channels::Rgb, &options),
+        Err(Error::InvalidImageLength { .. })
+    ));
+}
+
+#[test]
+fn test_delta() {
+    let mut rng = StdRng::seed_from_u64(20);
+    let (w, h) = (64, 48);
+    for &channels in &[3, 4] {
+        let prev = (0..w * h * channels).map(|_| rng.gen::<u8>()).collect::<Vec<_>>();
+        let mut next = prev.clone();
+        let rects = [Rect::new(3, 4, 10, 2), Rect::new(40, 30, 5, 7), Rect::new(63, 47, 1, 1)];
+        for rect in &rects {
+            for y in rect.y..rect.bottom() {
+                for x in rect.x..rect.right() {
+                    next[(y * w + x) as usize * channels as usize + 1] ^= 0x80;
+                }
+            }
+        }
+        let encoder = Encoder::new(&next, w, h).unwrap();
+        let delta = encoder.encode_delta_to_vec(&prev).unwrap();
+        assert!(delta.len() < next.len() / 10);
+        let mut frame = prev.clone();
+        assert_eq!(apply_delta(&delta, &mut frame).unwrap(), rects.to_vec());
+        assert_eq!(frame, next);
+
+        // nearby changes can be merged into fewer patches
+        let options = IslandOptions::new().with_merge_distance(Some(30));
+        let encoder = Encoder::new(&next, w, h).unwrap().with_island_options(options);
+        let mut frame = prev.clone();
+        let updated = apply_delta(encoder.encode_delta_to_vec(&prev).unwrap(), &mut frame).unwrap();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(frame, next);
+
+        let empty = Encoder::new(&prev, w, h).unwrap().encode_delta_to_vec(&prev).unwrap();
+        assert!(apply_delta(&empty, &mut frame).unwrap().is_empty());
+        assert_eq!(frame, next);
+    }
+}
+
+#[test]
+fn test_delta_invalid() {
+    let (w, h) = (8, 8);
+    let prev = vec![0_u8; 8 * 8 * 4];
+    let mut next = prev.clone();
+    next[(3 * 8 + 5) * 4] = 1;
+    let delta = Encoder::new(&next, w, h).unwrap().encode_delta_to_vec(&prev).unwrap();
+    let mut frame = prev.clone();
+
+    let mut invalid = delta.clone();
+    invalid[3] = b'f';
+    assert!(matches!(apply_delta(&invalid, &mut frame), Err(Error::InvalidDeltaMagic { .. })));
+    assert!(matches!(
+        apply_delta(&delta, &mut frame[4..]),
+        Err(Error::InvalidImageLength { .. })
+    ));
+    assert!(matches!(
+        apply_delta(&delta[..delta.len() - 1], &mut frame),
+        Err(Error::UnexpectedBufferEnd)
+    ));
+    let mut invalid = delta.clone();
+    invalid[17..21].copy_from_slice(&8_u32.to_be_bytes()); // x of the first patch
+    assert!(matches!(apply_delta(&invalid, &mut frame), Err(Error::InvalidRegion { .. })));
+    assert_eq!(frame, prev);
+    assert!(matches!(
+        Encoder::new(&next, w, h).unwrap().encode_delta_to_vec(&prev[4..]),
+        Err(Error::InvalidImageLength { .. })
+    ));
+}
+
+#[test]
+fn test_island_tracker() {
+    let frame = |rects: &[Rect]| rects.iter().map(|&r| Island::new(r)).collect::<Vec<_>>();
+    let ids = |islands: &[Island]| islands.iter().map(|i| i.id.unwrap()).collect::<Vec<_>>();
+    let mut tracker = IslandTracker::new();
+
+    let (a, b, c) = (Rect::new(0, 0, 4, 4), Rect::new(10, 0, 3, 3), Rect::new(0, 10, 2, 2));
+    let mut first = frame(&[a, b, c]);
+    let events = tracker.update(&mut first);
+    assert_eq!(ids(&first), vec![1, 2, 3]);
+    assert_eq!(events, vec![
+        IslandEvent::Appeared { id: 1, rect: a },
+        IslandEvent::Appeared { id: 2, rect: b },
+        IslandEvent::Appeared { id: 3, rect: c },
+    ]);
+
+    // a moves, b splits in two, c disappears, d appears
+    let (a2, b1, b2) = (Rect::new(1, 0, 4, 4), Rect::new(10, 0, 1, 3), Rect::new(12, 0, 1, 3));
+    let d = Rect::new(20, 20, 1, 1);
+    let mut second = frame(&[a2, b1, b2, d]);
+    let events = tracker.update(&mut second);
+    assert_eq!(ids(&second), vec![1, 2, 4, 5]);
+    assert_eq!(events, vec![
+        IslandEvent::Moved { id: 1, from: a, to: a2 },
+        IslandEvent::Moved { id: 2, from: b, to: b1 },
+        IslandEvent::Appeared { id: 5, rect: d },
+        IslandEvent::Split { id: 2, into: vec![4] },
+        IslandEvent::Disappeared { id: 3, rect: c },
+    ]);
+
+    // b merges back, everything else stays in place
+    let mut third = frame(&[a2, b, d]);
+    let events = tracker.update(&mut third);
+    assert_eq!(ids(&third), vec![1, 2, 5]);
+    assert_eq!(events, vec![
+        IslandEvent::Moved { id: 2, from: b1, to: b },
+        IslandEvent::Merged { id: 2, from: vec![4] },
+    ]);
+    assert_eq!(tracker.islands(), &third[..]);
+
+    // a jump with too little overlap is a different island
+    let mut tracker = tracker.with_min_iou(0.5);
+    let mut fourth = frame(&[Rect::new(3, 0, 4, 4), b, d]);
+    let events = tracker.update(&mut fourth);
+    assert_eq!(ids(&fourth), vec![6, 2, 5]);
+    assert_eq!(events[..2], [
+        IslandEvent::Appeared { id: 6, rect: Rect::new(3, 0, 4, 4) },
+        IslandEvent::Disappeared { id: 1, rect: a2 },
+    ]);
+    tracker.reset();
+    let events = tracker.update(&mut fourth);
+    assert_eq!(ids(&fourth), vec![7, 8, 9]);
+    assert_eq!(events.len(), 3);
+}
+
+#[test]
+fn test_island_tracker_encoded() {
+    let frames = [
+        ["............", ".##.........", ".##.....#...", "........#...", "............"],
+        ["............", "..##........", "..##........", "........#...", "........#..."],
+    ];
+    let mut tracker = IslandTracker::new();
+    let mut decoded = Vec::new();
+    for (k, rows) in frames.iter().enumerate() {
+        let (mask, w, h) = mask_from_str(rows);
+        let pixels = rgba_from_mask(&mask);
+        let options = IslandOptions::new().with_stats(true).with_streams(true);
+        let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+        let (encoded, events) = encoder.encode_tracked_to_vec(&mut tracker).unwrap();
+        assert_eq!(events.len(), 2, "frame {}", k);
+        let mut decoder = Decoder::new(&encoded).unwrap();
+        assert!(decoder.header().has_island_ids());
+        let islands = decoder.islands().unwrap();
+        assert_eq!(islands.islands, tracker.islands());
+        assert!(islands.iter().all(|island| island.stats.is_some()));
+        assert_eq!(decoder.decode_all_islands().unwrap().len(), 2);
+        assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+        decoded.push(islands.iter().map(|island| (island.rect, island.id)).collect::<Vec<_>>());
+    }
+    let (a, b) = (Some(1), Some(2));
+    assert_eq!(decoded[0], vec![(Rect::new(1, 1, 2, 2), a), (Rect::new(8, 2, 1, 2), b)]);
+    assert_eq!(decoded[1], vec![(Rect::new(2, 1, 2, 2), a), (Rect::new(8, 3, 1, 2), b)]);
+
+    let encoded = encode_to_vec(rgba_from_mask(&[true, false]), 2, 1, true).unwrap();
+    assert!(!Decoder::new(&encoded).unwrap().header().has_island_ids());
+}
+
+#[test]
+fn test_island_index() {
+    let mut rng = StdRng::seed_from_u64(22);
+    let distance2 = |r: &Rect, x: u32, y: u32| {
+        let axis = |v: i64, start: i64, end: i64| (start - v).max(v - end + 1).max(0);
+        let dx = axis(x.into(), r.x.into(), r.right().into());
+        let dy = axis(y.into(), r.y.into(), r.bottom().into());
+        dx * dx + dy * dy
+    };
+    for &overlapping in &[false, true] {
+        let (w, h) = (200, 150);
+        let rects = (0..60)
+            .map(|_| {
+                let size = if overlapping { 40 } else { 8 };
+                let (rw, rh) = (rng.gen_range(1..size), rng.gen_range(1..size));
+                Rect::new(rng.gen_range(10..w - rw), rng.gen_range(10..h - rh), rw, rh)
+            })
+            .collect::<Vec<_>>();
+        let islands = rects.iter().map(|&r| Island::new(r)).collect::<Vec<_>>();
+        for index in &[IslandIndex::new(&islands), IslandIndex::with_cell_size(&islands, 3)] {
+            assert_eq!(index.len(), rects.len());
+            for _ in 0..500 {
+                let (x, y) = (rng.gen_range(0..w + 20), rng.gen_range(0..h + 20));
+                let expected = (0..rects.len()).filter(|&i| rects[i].contains(x, y));
+                assert_eq!(index.at(x, y), expected.collect::<Vec<_>>());
+
+                let query = Rect::new(x, y, rng.gen_range(1..50), rng.gen_range(1..50));
+                let expected = (0..rects.len()).filter(|&i| rects[i].intersects(&query));
+                assert_eq!(index.intersecting(&query), expected.collect::<Vec<_>>());
+
+                let nearest = (0..rects.len()).min_by_key(|&i| (distance2(&rects[i], x, y), i));
+                assert_eq!(index.nearest(x, y), nearest, "{} {}", x, y);
+            }
+        }
+    }
+    let index = Islands::default().index();
+    assert!(index.is_empty());
+    assert!(index.at(0, 0).is_empty() && index.intersecting(&Rect::new(0, 0, 9, 9)).is_empty());
+    assert_eq!(index.nearest(0, 0), None);
+}
+
+#[test]
+fn test_overlay() {
+    let rows = [
+        "..........",
+        ".#####....",
+        ".#####....",
+        ".#####..#.",
+        ".#####....",
+        "..........",
+    ];
+    let (mask, w, h) = mask_from_str(&rows);
+    let encoded = encode_to_vec(rgba_from_mask(&mask), w, h, true).unwrap();
+    let (header, mut pixels, islands) = decode_qoi(&encoded).unwrap();
+    // '#' for outline pixels, '+' for other island pixels
+    let outlined = |pixels: &[u8], color: &[u8]| -> String {
+        let drawn = pixels.chunks_exact(color.len()).map(|px| px == color);
+        drawn.zip(&mask).map(|(d, &fg)| if d { '#' } else if fg { '+' } else { '.' }).collect()
+    };
+
+    let options = OverlayOptions::new().with_palette(vec![[1, 2, 3, 4], [5, 6, 7, 8]]);
+    draw_islands(&mut pixels, &header, &islands, &options).unwrap();
+    let expected = [
+        "..........",
+        ".#####....",
+        ".#+++#....",
+        ".#+++#..+.",
+        ".#####....",
+        "..........",
+    ];
+    assert_eq!(outlined(&pixels, &[1, 2, 3, 4]), expected.concat());
+    assert_eq!(&pixels[(3 * 10 + 8) * 4..][..4], &[5, 6, 7, 8]);
+    assert_eq!(&pixels[(2 * 10 + 2) * 4..][..4], &[200, 100, 50, 255]);
+
+    // thick outlines fill small islands, and RGB buffers are supported as well
+    let mut decoder = Decoder::new(&encoded).unwrap().with_channels(Channels::Rgb);
+    let mut rgb = decoder.decode_to_vec().unwrap();
+    let options = OverlayOptions::new().with_color([9, 9, 9, 255]).with_thickness(2);
+    draw_islands(&mut rgb, &header, &islands, &options).unwrap();
+    assert_eq!(outlined(&rgb, &[9, 9, 9]), rows.concat());
+    assert!(matches!(
+        draw_islands(&mut rgb[1..], &header, &islands, &options),
+        Err(Error::InvalidImageLength { .. })
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_coco_annotations() {
+    use qoi::coco_annotations;
+    use serde_json::json;
+
+    let (mask, w, h) = mask_from_str(&["......", ".##...", ".#..##", "....##"]);
+    let pixels = rgba_from_mask(&mask);
+    let encode = |options: IslandOptions| {
+        let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+        encoder.encode_to_vec(true).unwrap()
+    };
+
+    let encoded = encode(IslandOptions::new().with_masks(true));
+    let coco = serde_json::to_value(coco_annotations(&encoded, 7, 3).unwrap()).unwrap();
+    let annotation = |id, bbox, area, segmentation| {
+        json!({
+            "id": id, "image_id": 7, "category_id": 3, "bbox": bbox, "area": area,
+            "segmentation": segmentation, "iscrowd": 0
+        })
+    };
+    let expected = json!({"annotations": [
+        annotation(1, [1, 1, 2, 2], 3, json!({"counts": [5, 2, 2, 1, 14], "size": [4, 6]})),
+        annotation(2, [4, 2, 2, 2], 4, json!({"counts": [18, 2, 2, 2], "size": [4, 6]})),
+    ]});
+    assert_eq!(coco, expected);
+
+    let encoded = encode(IslandOptions::new().with_contours(true));
+    let coco = serde_json::to_value(coco_annotations(&encoded, 7, 3).unwrap()).unwrap();
+    let expected = json!({"annotations": [
+        annotation(1, [1, 1, 2, 2], 4, json!([[1, 1, 3, 1, 3, 2, 2, 2, 2, 3, 1, 3]])),
+        annotation(2, [4, 2, 2, 2], 4, json!([[4, 2, 6, 2, 6, 4, 4, 4]])),
+    ]});
+    assert_eq!(coco, expected);
+
+    let coco = coco_annotations(encode_to_vec(&pixels, w, h, true).unwrap(), 7, 3).unwrap();
+    assert!(coco.annotations.iter().all(|a| a.segmentation.is_none()));
+    assert_eq!(coco.annotations[0].area, 4);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_roundtrip() {
+    let mut rng = StdRng::seed_from_u64(17);
+    let (w, h) = (30, 20);
+    let pixels = noisy_islands_image(&mut rng, w, h);
+    let options = IslandOptions::new().with_stats(true).with_masks(true).with_contours(true);
+    let mut encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options);
+    let encoded = encoder.encode_to_vec(true).unwrap();
+    let (header, _, islands) = decode_qoi(&encoded).unwrap();
+    let json = serde_json::to_string(&(header, &islands)).unwrap();
+    let (header2, islands2): (qoi::Header, Islands) = serde_json::from_str(&json).unwrap();
+    assert_eq!(header2, header);
+    assert_eq!(islands2, islands);
+}
+
+#[test]
+fn test_sprites() {
+    // 'o' pixels are fully transparent, but still foreground with the default options
+    let rows = [
+        "..........",
+        ".o##...#..",
+        ".o##......",
+        ".ooo......",
+        "..........",
+    ];
+    let (w, h) = (rows[0].len() as u32, rows.len() as u32);
+    let pixels = rows
+        .concat()
+        .chars()
+        .flat_map(|c| match c {
+            '#' => [200, 100, 50, 255],
+            'o' => [1, 1, 1, 0],
+            _ => [0; 4],
+        })
+        .collect::<Vec<_>>();
+    let decoded = |sprite: &[u8]| {
+        let (header, pixels, islands) = decode_qoi(sprite).unwrap();
+        assert!(islands.is_empty());
+        (header.width, header.height, pixels)
+    };
+
+    let options = SpriteOptions::new();
+    let sprites = extract_sprites(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    assert_eq!(sprites.manifest, vec![Rect::new(1, 1, 3, 3), Rect::new(7, 1, 1, 1)]);
+    let (sw, sh, sprite) = decoded(&sprites.images[0]);
+    assert_eq!((sw, sh), (3, 3));
+    assert_eq!(&sprite[..4], &[1, 1, 1, 0]);
+    assert_eq!(decoded(&sprites.images[1]), (1, 1, vec![200, 100, 50, 255]));
+
+    // trimming drops transparent borders, padding adds transparent ones
+    let options = SpriteOptions::new().with_trim(true).with_padding(1);
+    let sprites = extract_sprites(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    assert_eq!(sprites.manifest, vec![Rect::new(2, 1, 2, 2), Rect::new(7, 1, 1, 1)]);
+    let (sw, sh, sprite) = decoded(&sprites.images[0]);
+    assert_eq!((sw, sh), (4, 4));
+    for (i, px) in sprite.chunks_exact(4).enumerate() {
+        let inside = (1..3).contains(&(i % 4)) && (1..3).contains(&(i / 4));
+        assert_eq!(px, if inside { [200, 100, 50, 255] } else { [0; 4] });
+    }
+
+    // encoded images reuse their island table, or detect islands if they have none
+    let options = SpriteOptions::new().with_trim(true);
+    let expected = extract_sprites(&pixels, w, h, Channels::Rgba, &options).unwrap();
+    let encoded = encode_to_vec(&pixels, w, h, true).unwrap();
+    assert_eq!(extract_sprites_from_qoi(&encoded, &options).unwrap(), expected);
+    let encoded = encode_to_vec(&pixels, w, h, false).unwrap();
+    assert_eq!(extract_sprites_from_qoi(&encoded, &options).unwrap(), expected);
+    let options = options.with_island_options(IslandOptions::new().with_merge_distance(Some(10)));
+    let sprites = extract_sprites_from_qoi(&encoded, &options).unwrap();
+    assert_eq!(sprites.manifest, vec![Rect::new(2, 1, 6, 2)]);
+
+    assert!(matches!(
+        extract_sprites(&pixels[4..], w, h, Channels::Rgba, &SpriteOptions::new()),
+        Err(Error::InvalidImageLength { .. })
+    ));
+}
+
+#[test]
+fn test_atlas() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let sizes = (0..40).map(|_| (rng.gen_range(1..20), rng.gen_range(1..20))).collect::<Vec<_>>();
+    let images = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &(w, h))| {
+            let pixels = vec![[i as u8 + 1, 2, 3, 255]; (w * h) as usize].concat();
+            encode_to_vec(&pixels, w, h, false).unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let padding = 2;
+    let atlas = pack_atlas(&images, &AtlasOptions::new().with_padding(padding)).unwrap();
+    let (header, pixels, islands) = decode_qoi(&atlas.data).unwrap();
+    assert_eq!(header.channels, Channels::Rgba);
+    assert!(header.has_island_ids());
+    assert_eq!(islands.len(), sizes.len());
+    let padded = |r: &Rect| Rect::new(r.x - padding, r.y - padding, r.width + 4, r.height + 4);
+    for island in &islands {
+        assert_eq!(island.rect, atlas.rects[island.id.unwrap() as usize - 1]);
+    }
+    for (i, rect) in atlas.rects.iter().enumerate() {
+        assert_eq!((rect.width, rect.height), sizes[i]);
+        // padding is kept to the edges of the atlas and between the images
+        assert!(padded(rect).right() <= header.width && padded(rect).bottom() <= header.height);
+        for other in &atlas.rects[..i] {
+            assert!(padded(rect).intersection(other).is_none());
+        }
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                let px = &pixels[(y * header.width + x) as usize * 4..][..4];
+                assert_eq!(px, &[i as u8 + 1, 2, 3, 255]);
+            }
+        }
+    }
+    let used = sizes.iter().map(|&(w, h)| (w + padding) * (h + padding)).sum::<u32>();
+    assert!(used * 2 > header.width * header.height);
+
+    // the island table is in raster order, so it works with the index and the tracker
+    let key = |island: &Island| (island.rect.y, island.rect.x);
+    assert!(islands.as_slice().windows(2).all(|pair| key(&pair[0]) <= key(&pair[1])));
+    let index = islands.index();
+    for (i, rect) in atlas.rects.iter().enumerate() {
+        let hits = index.at(rect.x, rect.y);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(islands.as_slice()[hits[0]].id, Some(i as u32 + 1));
+    }
+    let mut tracker = IslandTracker::new();
+    let mut frame = islands.islands.clone();
+    let _ = tracker.update(&mut frame);
+    let mut next = islands.islands.clone();
+    assert_eq!(tracker.update(&mut next), vec![]);
+    assert_eq!(next, frame);
+
+    // RGB images make an RGB atlas
+    let rgb = encode_to_vec([7_u8; 4 * 3 * 3], 4, 3, false).unwrap();
+    let atlas = pack_atlas(&[&rgb, &rgb], &AtlasOptions::new()).unwrap();
+    let (header, pixels, _) = decode_qoi(&atlas.data).unwrap();
+    assert_eq!(header.channels, Channels::Rgb);
+    assert_eq!((header.width, header.height), (4, 6));
+    assert_eq!(pixels, vec![7; 4 * 6 * 3]);
+    assert_eq!(atlas.rects, vec![Rect::new(0, 0, 4, 3), Rect::new(0, 3, 4, 3)]);
+
+    // the atlas is widened if it's too tall
+    let options = AtlasOptions::new().with_max_size(8, 5);
+    assert_eq!(pack_atlas(&[&rgb, &rgb], &options).unwrap().rects[1], Rect::new(4, 0, 4, 3));
+    let options = AtlasOptions::new().with_max_size(3, 100);
+    assert!(matches!(pack_atlas(&[&rgb, &rgb], &options), Err(Error::AtlasTooSmall { .. })));
+    let options = AtlasOptions::new().with_max_size(7, 5);
+    assert!(matches!(pack_atlas(&[&rgb, &rgb], &options), Err(Error::AtlasTooSmall { .. })));
+
+    // islands of a single image come out the same as its sprites
+    let rows = ["#..##.", "...##.", "......", ".####."];
+    let (mask, w, h) = mask_from_str(&rows);
+    let encoded = encode_to_vec(rgba_from_mask(&mask), w, h, true).unwrap();
+    let (atlas, sources) =
+        pack_islands(&encoded, &SpriteOptions::new(), &AtlasOptions::new()).unwrap();
+    let sprites = extract_sprites_from_qoi(&encoded, &SpriteOptions::new()).unwrap();
+    assert_eq!(sources, sprites.manifest);
+    assert_eq!(pack_atlas(&sprites.images, &AtlasOptions::new()).unwrap(), atlas);
+}
+
+#[test]
+fn test_trim() {
+    let rows = [
+        "..........",
+        "..........",
+        "...##.....",
+        "...#......",
+        ".......#..",
+        "..........",
+    ];
+    let (mask, w, h) = mask_from_str(&rows);
+    let pixels = rgba_from_mask(&mask);
+    let options = IslandOptions::new().with_stats(true).with_streams(true);
+    let (_, full_islands) = Encoder::new(&pixels, w, h)
+        .unwrap()
+        .with_island_options(options.clone())
+        .encode_to_vec_with_islands(true)
+        .unwrap();
+
+    let encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options.clone());
+    let mut encoder = encoder.with_trim(Trim::Islands);
+    let (encoded, islands) = encoder.encode_to_vec_with_islands(true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap();
+    let header = *decoder.header();
+    assert!(header.is_trimmed());
+    assert_eq!(encoder.header(), &header);
+
+    // the encoder still trims the whole image when used again
+    assert_eq!(encoder.encode_to_vec(true).unwrap(), encoded);
+    let mut buf = vec![0; encoder.required_buf_len()];
+    let n = encoder.encode_to_buf(&mut buf, true).unwrap();
+    assert_eq!(&buf[..n], encoded.as_slice());
+    let n = encoder.encode_to_buf(&mut buf[..n - 1], true);
+    assert!(matches!(n, Err(Error::OutputBufferTooSmall { .. })));
+    assert_eq!((header.width, header.height), (5, 3));
+    let canvas = decoder.canvas().unwrap();
+    assert_eq!(canvas, Canvas { width: w, height: h, x: 3, y: 2 });
+    assert_eq!(canvas.rect(&header), Rect::new(3, 2, 5, 3));
+    assert_eq!(decoder.decode_to_vec().unwrap().len(), 5 * 3 * 4);
+
+    // islands are stored relative to the crop, and sub-streams are still found
+    assert_eq!(decoder.islands().unwrap(), islands);
+    assert_eq!(islands.len(), full_islands.len());
+    for (island, full) in islands.iter().zip(full_islands.iter()) {
+        let Rect { x, y, width, height } = island.rect;
+        assert_eq!(Rect::new(x + 3, y + 2, width, height), full.rect);
+        let (stats, full) = (island.stats.unwrap(), full.stats.unwrap());
+        assert_eq!(stats.pixel_count, full.pixel_count);
+        assert!((stats.centroid.0 + 3. - full.centroid.0).abs() < 1e-5);
+        assert!((stats.centroid.1 + 2. - full.centroid.1).abs() < 1e-5);
+    }
+    let island = decoder.decode_island_to_vec(1).unwrap();
+    assert_eq!(island, vec![200, 100, 50, 255]);
+
+    // the full canvas can be restored with a transparent fill
+    let mut decoder = Decoder::new(&encoded).unwrap().with_restore_canvas(true);
+    assert_eq!(decoder.required_buf_len(), pixels.len());
+    assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+    let decoder = Decoder::new(&encoded).unwrap().with_restore_canvas(true);
+    let mut decoder = decoder.with_channels(Channels::Rgb);
+    let rgb = pixels.chunks_exact(4).flat_map(|px| px[..3].to_vec()).collect::<Vec<_>>();
+    assert_eq!(decoder.decode_to_vec().unwrap(), rgb);
+
+    // islands are decoded from the stored crop, with or without sub-streams
+    let expected = full_islands.iter().map(|isl| crop(&pixels, w, 4, isl.rect)).collect::<Vec<_>>();
+    let mut decoder = Decoder::new(&encoded).unwrap().with_restore_canvas(true);
+    assert_eq!(decoder.decode_all_islands().unwrap(), expected);
+    let encoder = Encoder::new(&pixels, w, h).unwrap();
+    let encoder = encoder.with_island_options(options.clone().with_streams(false));
+    let encoded = encoder.with_trim(Trim::Islands).encode_to_vec(true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap().with_restore_canvas(true);
+    assert!(!decoder.header().has_island_streams());
+    assert_eq!(decoder.decode_all_islands().unwrap(), expected);
+    assert_eq!(decoder.decode_to_vec().unwrap(), pixels);
+
+    // foreground trimming includes pixels of filtered out islands
+    let options = options.with_min_area(2);
+    let trimmed = |trim| {
+        let encoder = Encoder::new(&pixels, w, h).unwrap().with_island_options(options.clone());
+        encoder.with_trim(trim).encode_to_vec(true).unwrap()
+    };
+    let encoded = trimmed(Trim::Islands);
+    let decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.canvas().unwrap().rect(decoder.header()), Rect::new(3, 2, 2, 2));
+    assert_eq!(decode_islands(&encoded).unwrap().len(), 1);
+    let encoded = trimmed(Trim::Foreground);
+    let decoder = Decoder::new(&encoded).unwrap();
+    assert_eq!(decoder.canvas().unwrap().rect(decoder.header()), Rect::new(3, 2, 5, 3));
+
+    // background detection uses the corner of the full image, even in sparse mode
+    let mut framed = pixels.clone();
+    framed.chunks_exact_mut(4).filter(|px| px[3] == 0).for_each(|px| px.copy_from_slice(&[9; 4]));
+    let options = IslandOptions::new().with_foreground(Foreground::Background { tolerance: 0 });
+    let encoder = Encoder::new(&framed, w, h).unwrap().with_island_options(options);
+    let mut encoder = encoder.with_trim(Trim::Islands).with_sparse([9; 4]);
+    let mut buf = vec![0; encoder.required_buf_len()];
+    let size = encoder.encode_to_buf(&mut buf, true).unwrap();
+    let encoded = &buf[..size];
+    assert_eq!(decode_islands(encoded).unwrap().len(), 2);
+    let mut decoder = Decoder::new(encoded).unwrap().with_restore_canvas(true);
+    let restored = decoder.decode_to_vec().unwrap();
+    let pairs = restored.chunks_exact(4).zip(framed.chunks_exact(4));
+    for (i, (restored, framed)) in pairs.enumerate() {
+        let inside = Rect::new(3, 2, 5, 3).contains(i as u32 % w, i as u32 / w);
+        assert_eq!(restored, if inside { framed } else { &[0; 4] });
+    }
+
+    // images without foreground keep a single pixel
+    let empty = vec![0; pixels.len()];
+    let encoded =
+        Encoder::new(&empty, w, h).unwrap().with_trim(Trim::Islands).encode_to_vec(true).unwrap();
+    let mut decoder = Decoder::new(&encoded).unwrap().with_restore_canvas(true);
+    assert_eq!((decoder.header().width, decoder.header().height), (1, 1));
+    assert_eq!(decoder.decode_to_vec().unwrap(), empty);
+
+    // the canvas must hold the image
+    let mut broken = encoded.clone();
+    let offset = 22 + decoder.header().n_encode() + 5;
+    broken[offset + 8..offset + 12].copy_from_slice(&w.to_be_bytes());
+    assert!(matches!(Decoder::new(&broken), Err(Error::InvalidRegion { .. })));
+}